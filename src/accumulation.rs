@@ -7,9 +7,23 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
-use super::{AccountAdded, AccountId, AccumulationEvent, RewardsAccumulated, RewardsClaimed};
+use super::{
+    api::{ApiAccount, ApiSnapshot, ApiTotals, API_SNAPSHOT_VERSION},
+    bloom::BloomFilter,
+    compact::encode_ids,
+    farming::RewardSchedule,
+    merkle::{leaf, Digest, MerkleProof},
+    AccountAdded, AccountClosed, AccountId, AccumulationEvent, ClaimRefunded, EscrowReleased,
+    RewardKind, RewardsAccumulated, RewardsClaimed, RewardsTransferred, WorkDecayed,
+};
+use log::warn;
 use safe_nd::{Error, Money, Result, RewardCounter, Work};
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+};
+use tiny_keccak::{Hasher, Sha3};
 
 /// The book keeping of rewards.
 /// The business rule is that a piece of data
@@ -17,258 +31,6190 @@ use std::collections::{HashMap, HashSet};
 #[derive(Clone)]
 pub struct Accumulation {
     idempotency: HashSet<Id>,
+    rewarded_order: VecDeque<Id>,
+    pruned_ids: u64,
+    idempotency_cap: Option<usize>,
+    idempotency_filter: Option<BloomFilter>,
+    filtered_ids: usize,
     accumulated: HashMap<AccountId, RewardCounter>,
+    store_costs: HashMap<AccountId, Money>,
+    pool: Option<Money>,
+    fingerprints: HashMap<AccountId, HashMap<Fingerprint, u64>>,
+    resets: Vec<AccountReset>,
+    history: HashMap<AccountId, AccountHistory>,
+    epoch_cap: Option<Money>,
+    epoch_rewards: HashMap<AccountId, Money>,
+    past_epochs: VecDeque<HashMap<AccountId, Money>>,
+    auto_claim_threshold: Option<Money>,
+    min_claim: Option<Money>,
+    last_reward_id: u64,
+    generations: HashMap<AccountId, u64>,
+    buffered: HashMap<Id, HashMap<AccountId, Money>>,
+    inflation_factor: Option<f64>,
+    escrow_until_age: Option<u64>,
+    epoch: u64,
+    joined: HashMap<AccountId, u64>,
+    escrowed: HashMap<AccountId, Money>,
+    refunded_claims: BTreeSet<(AccountId, u64)>,
+    staged: Vec<AccumulationEvent>,
+    max_accounts: Option<usize>,
+    max_balance: Option<Money>,
+    require_registered: bool,
+    undo_log: VecDeque<UndoRecord>,
+    overflow_mode: OverflowMode,
+    schedule: RewardSchedule,
+    next_seq: u64,
+    subscribers: Subscribers,
 }
 
+/// How rewards overflowing the balance of an account are handled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverflowMode {
+    /// The accumulation is rejected with `Error::ExcessiveValue`. This is the default.
+    Checked,
+    /// The balance is clamped at the max value, and the excess is lost.
+    Saturating,
+}
+
+/// The number of past epochs for which the
+/// rewards per account are kept, see `reward_velocity`.
+pub const RETAINED_EPOCHS: usize = 32;
+
+/// The number of most recently applied events
+/// which can be undone, see `undo_last`.
+pub const RETAINED_UNDO: usize = 16;
+
+/// The number of most recent rewards per account
+/// kept in its history, see `statement`.
+pub const RETAINED_CONTRIBUTIONS: usize = 64;
+
 /// Identification type
 pub type Id = Vec<u8>;
 
-impl Accumulation {
-    /// ctor
-    pub fn new(idempotency: HashSet<Id>, accumulated: HashMap<AccountId, RewardCounter>) -> Self {
-        Self {
-            idempotency,
-            accumulated,
+/// Identifies logical work, which may be
+/// rewarded under different ids.
+pub type Fingerprint = Vec<u8>;
+
+/// A callback invoked with every applied event, see `Accumulation::subscribe`.
+pub type Subscriber = Box<dyn FnMut(&AccumulationEvent) + Send + Sync>;
+
+/// The subscribers of an instance. They are not cloned, so that events
+/// applied to clones (e.g. when validating on a candidate state) are not observed.
+#[derive(Default)]
+struct Subscribers(Vec<Subscriber>);
+
+impl Clone for Subscribers {
+    fn clone(&self) -> Self {
+        Default::default()
+    }
+}
+
+/// The lifetime activity of an account.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct AccountHistory {
+    earned: Money,
+    claimed: Money,
+    rewards: u64,
+    /// The most recent rewards, each with the rewarded id and the position of the id
+    /// in the order of rewarded ids, counting pruned ids, see `prune_idempotency`.
+    contributions: VecDeque<Contribution>,
+}
+
+type Contribution = (Id, Money, u64);
+
+/// The state touched by an applied event, as it was before.
+#[derive(Clone, Debug)]
+struct UndoRecord {
+    accounts: Vec<AccountUndo>,
+    pool: Option<Money>,
+    last_reward_id: u64,
+    rewarded: Vec<Id>,
+    buffered: Vec<(Id, HashMap<AccountId, Money>)>,
+    claimed: Money,
+    /// The epoch, its rewards and the past epochs, if the event entered a later epoch.
+    epochs: Option<EpochsUndo>,
+    /// The claim refunded by the event, if any.
+    refunded_claim: Option<(AccountId, u64)>,
+}
+
+type EpochsUndo = (
+    u64,
+    HashMap<AccountId, Money>,
+    VecDeque<HashMap<AccountId, Money>>,
+);
+
+/// The state of an account, as it was before an applied event.
+#[derive(Clone, Debug)]
+struct AccountUndo {
+    account: AccountId,
+    accumulated: Option<RewardCounter>,
+    escrowed: Option<Money>,
+    epoch_rewards: Option<Money>,
+    store_cost: Option<Money>,
+    // earned, claimed, number of rewards, and the oldest
+    // contribution if the window of contributions is full
+    history: Option<(Money, Money, u64, Option<Contribution>)>,
+    generation: Option<u64>,
+    joined: Option<u64>,
+    // the fingerprint, if the event added it
+    fingerprint: Option<Fingerprint>,
+}
+
+/// A summary of all activity of an account.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccountStatement {
+    /// The account id.
+    pub account: AccountId,
+    /// The currently accumulated, unclaimed reward.
+    pub balance: Money,
+    /// The current work.
+    pub work: Work,
+    /// Total rewards accumulated over the lifetime of the account.
+    pub lifetime_earned: Money,
+    /// Total rewards claimed over the lifetime of the account.
+    pub lifetime_claimed: Money,
+    /// The number of rewards to the account over its lifetime.
+    pub rewards: u64,
+    /// The most recent rewards to the account, at most `RETAINED_CONTRIBUTIONS`,
+    /// with the id each was rewarded for, oldest first. Rewards for pruned ids
+    /// are dropped, see `Accumulation::prune_idempotency`.
+    pub contributions: Vec<(Id, Money)>,
+}
+
+/// Audit record of an operator overriding an account.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccountReset {
+    /// The account id.
+    pub account: AccountId,
+    /// The entry before the reset, if any.
+    pub previous: Option<RewardCounter>,
+    /// The entry after the reset.
+    pub current: RewardCounter,
+    /// Why the operator reset the account.
+    pub reason: String,
+}
+
+/// Mints the money backing the rewards, on an external ledger.
+pub trait Mint {
+    /// Mints the total, returning an error if minting failed.
+    fn mint(&self, total: Money) -> Result<()>;
+}
+
+/// Receipt of money minted on an external ledger,
+/// to be distributed as rewards.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintReceipt {
+    /// Identifies the mint transaction.
+    pub transaction: Vec<u8>,
+    /// The minted amount.
+    pub amount: Money,
+}
+
+/// The economics of claiming the rewards of an account,
+/// given an estimated fee for settling the claim.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ClaimEconomics {
+    /// The accumulated reward of the account.
+    pub balance: Money,
+    /// The estimated settlement fee.
+    pub fee: Money,
+    /// The proceeds after the fee, zero when the fee exceeds the balance.
+    pub net: Money,
+    /// Whether the fee exceeds the balance.
+    pub uneconomical: bool,
+}
+
+/// The result of a full integrity scan of the state, see `Accumulation::audit`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditReport {
+    /// The sum of all balances, `None` if it overflows.
+    pub total: Option<Money>,
+    /// The sum of all escrowed rewards, `None` if it overflows.
+    pub total_escrowed: Option<Money>,
+    /// Accounts whose rewards in the current epoch exceed the epoch cap, sorted.
+    pub over_cap: Vec<AccountId>,
+    /// Ids rewarded according to the account history,
+    /// but missing from the rewarded ids, sorted. Pruned ids are not included.
+    pub unrecorded_ids: Vec<Id>,
+    /// Ids pending in the buffer, but already rewarded, sorted.
+    pub rewarded_buffered_ids: Vec<Id>,
+    /// Accounts whose balance exceeds the max balance, sorted.
+    pub over_max_balance: Vec<AccountId>,
+    /// The number of rewarded ids pruned so far, see `Accumulation::prune_idempotency`.
+    pub pruned_ids: u64,
+}
+
+impl AuditReport {
+    /// Whether no inconsistency was found.
+    pub fn is_healthy(&self) -> bool {
+        self.total.is_some()
+            && self.total_escrowed.is_some()
+            && self.over_cap.is_empty()
+            && self.unrecorded_ids.is_empty()
+            && self.rewarded_buffered_ids.is_empty()
+            && self.over_max_balance.is_empty()
+    }
+}
+
+/// A cheap summary of the state, for monitoring, see `Accumulation::metrics`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccumulationMetrics {
+    /// The number of accounts with a balance.
+    pub accounts: usize,
+    /// The sum of all balances, `None` if it overflows.
+    pub total: Option<Money>,
+    /// The number of ids kept for idempotency.
+    pub rewarded_ids: usize,
+    /// The highest balance, zero when there are no accounts.
+    pub max_balance: Money,
+    /// The lowest balance, zero when there are no accounts.
+    pub min_balance: Money,
+    /// The mean balance, rounded down, zero when there are no accounts.
+    pub mean_balance: Money,
+}
+
+/// The serializable state of an `Accumulation`, for persisting
+/// it or transmitting it between nodes. See `Accumulation::snapshot`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AccumulationSnapshot {
+    /// The rewarded ids, oldest first.
+    pub rewarded: Vec<Id>,
+    /// The accumulated rewards of all accounts.
+    #[serde(with = "crate::account_pairs")]
+    pub accumulated: HashMap<AccountId, RewardCounter>,
+    /// The sequence number of the next event.
+    pub next_seq: u64,
+    /// The part of the balances rewarded for store cost, see `Accumulation::balance_of`.
+    #[serde(with = "crate::account_pairs", default)]
+    pub store_costs: HashMap<AccountId, Money>,
+    /// The rewards held in escrow, see `Accumulation::with_escrow_until_age`.
+    #[serde(with = "crate::account_pairs", default)]
+    pub escrowed: HashMap<AccountId, Money>,
+    /// The epochs the accounts joined in, from which the escrow age is counted.
+    #[serde(with = "crate::account_pairs", default)]
+    pub joined: HashMap<AccountId, u64>,
+    /// The current epoch.
+    #[serde(default)]
+    pub epoch: u64,
+    /// The accounts and seqs of the refunded claims, see `Accumulation::refund_claim`.
+    #[serde(default)]
+    pub refunded_claims: BTreeSet<(AccountId, u64)>,
+}
+
+impl Accumulation {
+    /// ctor
+    pub fn new(idempotency: HashSet<Id>, accumulated: HashMap<AccountId, RewardCounter>) -> Self {
+        let rewarded_order = idempotency.iter().cloned().collect();
+        Self {
+            idempotency,
+            rewarded_order,
+            pruned_ids: 0,
+            idempotency_filter: None,
+            filtered_ids: 0,
+            idempotency_cap: None,
+            accumulated,
+            store_costs: Default::default(),
+            pool: None,
+            fingerprints: Default::default(),
+            resets: vec![],
+            history: Default::default(),
+            epoch_cap: None,
+            epoch_rewards: Default::default(),
+            past_epochs: Default::default(),
+            auto_claim_threshold: None,
+            min_claim: None,
+            last_reward_id: 0,
+            generations: Default::default(),
+            buffered: Default::default(),
+            inflation_factor: None,
+            escrow_until_age: None,
+            epoch: 0,
+            joined: Default::default(),
+            escrowed: Default::default(),
+            refunded_claims: Default::default(),
+            staged: vec![],
+            max_accounts: None,
+            max_balance: None,
+            require_registered: false,
+            undo_log: Default::default(),
+            overflow_mode: OverflowMode::Checked,
+            schedule: Default::default(),
+            next_seq: 0,
+            subscribers: Default::default(),
+        }
+    }
+
+    /// Loads state of the older format, which only had the balances.
+    /// The set of rewarded ids starts out empty, so until it has been restored
+    /// (e.g. with `compact::decode_ids`, or by replaying events), already
+    /// rewarded data can be rewarded again.
+    pub fn from_legacy(balances: HashMap<AccountId, RewardCounter>) -> Self {
+        warn!(
+            "Loaded {} legacy balances without rewarded ids. Data can be rewarded twice until the ids are restored.",
+            balances.len()
+        );
+        Self::new(Default::default(), balances)
+    }
+
+    /// Rebuilds the state by applying the events in order, starting from empty state.
+    /// Returns the first error from applying an event.
+    pub fn from_events(events: impl IntoIterator<Item = AccumulationEvent>) -> Result<Self> {
+        let mut accumulation = Self::new(Default::default(), Default::default());
+        for event in events {
+            accumulation.apply(event)?;
+        }
+        Ok(accumulation)
+    }
+
+    /// Restores the rewarded ids and accumulated rewards of a snapshot.
+    /// Configuration and history are not part of the snapshot, and start out as with `new`.
+    /// Neither are the kinds of the rewards, so all restored balances count as farming.
+    pub fn from_snapshot(snapshot: AccumulationSnapshot) -> Self {
+        let mut accumulation = Self::new(Default::default(), snapshot.accumulated);
+        for id in snapshot.rewarded {
+            accumulation.record_rewarded(id);
+        }
+        accumulation.next_seq = snapshot.next_seq;
+        accumulation.store_costs = snapshot.store_costs;
+        accumulation.escrowed = snapshot.escrowed;
+        accumulation.joined = snapshot.joined;
+        accumulation.epoch = snapshot.epoch;
+        accumulation.refunded_claims = snapshot.refunded_claims;
+        accumulation
+    }
+
+    /// Rewards are drawn from a prepaid pool with the given balance,
+    /// instead of being minted freely. Accumulations exceeding
+    /// the remaining pool balance are rejected.
+    pub fn with_reward_pool(mut self, balance: Money) -> Self {
+        self.pool = Some(balance);
+        self
+    }
+
+    /// Caps the rewards each account can accumulate per epoch.
+    /// Accumulations that would take an account above the cap in the current
+    /// epoch are rejected with `Error::InvalidOperation`. See `advance_epoch`.
+    pub fn with_account_epoch_cap(mut self, cap: Money) -> Self {
+        self.epoch_cap = Some(cap);
+        self
+    }
+
+    /// Accounts reaching the threshold in `accumulate_with_auto_claim`
+    /// get their rewards claimed along with the accumulation.
+    pub fn with_auto_claim_threshold(mut self, threshold: Money) -> Self {
+        self.auto_claim_threshold = Some(threshold);
+        self
+    }
+
+    /// Rejects claims of less than the minimum with `Error::InsufficientBalance`,
+    /// as claiming dust costs more than it pays out. The balance is kept until
+    /// it is worth claiming. This includes claims by `accumulate_with_auto_claim`.
+    pub fn with_min_claim(mut self, min: Money) -> Self {
+        self.min_claim = Some(min);
+        self
+    }
+
+    /// Scales every distribution by the factor at `accumulate`, for modelling
+    /// emission rates. Amounts are rounded down to whole nanos, and
+    /// accumulations where a scaled amount overflows are rejected.
+    pub fn with_inflation_factor(mut self, factor: f64) -> Self {
+        self.inflation_factor = Some(factor);
+        self
+    }
+
+    /// Holds the rewards of accounts younger than the given number of epochs
+    /// in escrow, instead of crediting them, until released by `release_escrow`.
+    /// The age of an account is counted from the epoch it was added or first rewarded.
+    pub fn with_escrow_until_age(mut self, epochs: u64) -> Self {
+        self.escrow_until_age = Some(epochs);
+        self
+    }
+
+    /// Limits the number of accounts. Adding accounts, explicitly or by
+    /// rewarding new ones, beyond the limit is rejected with `Error::TooManyEntries`.
+    /// Recipients with rewards held in escrow count as accounts, so that
+    /// releasing the escrow never exceeds the limit.
+    pub fn with_max_accounts(mut self, max: usize) -> Self {
+        self.max_accounts = Some(max);
+        self
+    }
+
+    /// Limits the balance of every account, as a policy against hoarding unclaimed
+    /// rewards. Accumulations raising a balance above the limit are rejected with
+    /// `Error::InvalidOperation`, unlike overflows, which are rejected with
+    /// `Error::ExcessiveValue`. Forfeiting a balance is limited the same way.
+    /// Rewards held in escrow are not limited, but are only released within the limit.
+    pub fn with_max_balance(mut self, max: Money) -> Self {
+        self.max_balance = Some(max);
+        self
+    }
+
+    /// Rejects accumulations to accounts which have not been added
+    /// with `add_account`, with `Error::NoSuchKey`. By default,
+    /// such accounts are created when rewarded.
+    pub fn with_registration_required(mut self) -> Self {
+        self.require_registered = true;
+        self
+    }
+
+    /// Sets how rewards overflowing the balance of an account
+    /// are handled by `accumulate` and `apply`, see `OverflowMode`.
+    pub fn with_overflow_mode(mut self, mode: OverflowMode) -> Self {
+        self.overflow_mode = mode;
+        self
+    }
+
+    /// Sets the schedule by which rewards halve as claims are paid out,
+    /// keeping the total claimed so far, see `RewardSchedule`.
+    pub fn with_reward_schedule(mut self, schedule: RewardSchedule) -> Self {
+        self.schedule = schedule.with_claimed(self.schedule.claimed());
+        self
+    }
+
+    /// Caps the number of rewarded ids kept, dropping the oldest when exceeded,
+    /// see `prune_idempotency`. Ids passed in at construction are
+    /// considered older than any rewarded after, in no particular order.
+    pub fn with_idempotency_cap(mut self, max: usize) -> Self {
+        self.idempotency_cap = Some(max);
+        self.prune_idempotency(max);
+        self
+    }
+
+    /// Keeps rewarded ids in the Bloom filter instead of storing them, bounding
+    /// the memory used for idempotency at the cost of false positives: an id
+    /// falsely found in the filter is considered rewarded, and so is never rewarded.
+    /// See `BloomFilter` for sizing the filter. The ids rewarded so far are moved into it.
+    /// Ids in the filter cannot be listed, so they are not part of snapshots, exports or
+    /// `orphaned_ids`, but they are counted, hashed by `state_hash` as the bits of the filter,
+    /// and merged into a filter of the same size, see `merge`. Undoing an event does not
+    /// remove its id from the filter.
+    pub fn with_idempotency_filter(mut self, mut filter: BloomFilter) -> Self {
+        for id in self.rewarded_order.drain(..) {
+            filter.insert(&id);
+            self.filtered_ids += 1;
+        }
+        self.idempotency.clear();
+        self.idempotency_filter = Some(filter);
+        self
+    }
+
+    /// -----------------------------------------------------------------
+    /// ---------------------- Queries ----------------------------------
+    /// -----------------------------------------------------------------
+
+    ///
+    pub fn get(&self, account: &AccountId) -> Option<&RewardCounter> {
+        self.accumulated.get(account)
+    }
+
+    /// Returns the part of the balance of the account rewarded of the kind.
+    /// Rewards credited without a kind, such as released from escrow,
+    /// forfeited by a closed account, or merged from another section, count as farming.
+    pub fn balance_of(&self, account: &AccountId, kind: RewardKind) -> Option<Money> {
+        let balance = self.get(account)?.reward;
+        let store_cost = self
+            .store_costs
+            .get(account)
+            .copied()
+            .unwrap_or_else(Money::zero)
+            .min(balance);
+        Some(match kind {
+            RewardKind::StoreCost => store_cost,
+            RewardKind::Farming => balance.checked_sub(store_cost).unwrap_or_else(Money::zero),
+        })
+    }
+
+    /// Returns whether the work of the account has reached its max value.
+    /// Work is incremented with every reward, and saturates at the max value
+    /// instead of wrapping around, so a saturated account no longer increases
+    /// its share of work relative to the others.
+    pub fn work_saturated(&self, account: &AccountId) -> bool {
+        match self.accumulated.get(account) {
+            None => false,
+            Some(acc) => acc.work == Work::MAX,
+        }
+    }
+
+    /// Returns the remaining balance of the reward pool,
+    /// or None if rewards are not drawn from a pool.
+    pub fn pool_balance(&self) -> Option<Money> {
+        self.pool
+    }
+
+    /// Returns what a claim of the account would net, after
+    /// the estimated fee, or None if the account has no rewards.
+    pub fn claim_economics(
+        &self,
+        account: &AccountId,
+        estimated_fee: Money,
+    ) -> Option<ClaimEconomics> {
+        let balance = self.accumulated.get(account)?.reward;
+        Some(ClaimEconomics {
+            balance,
+            fee: estimated_fee,
+            net: balance
+                .checked_sub(estimated_fee)
+                .unwrap_or_else(Money::zero),
+            uneconomical: estimated_fee > balance,
+        })
+    }
+
+    /// Returns the operator resets done on this instance, oldest first.
+    pub fn resets(&self) -> &[AccountReset] {
+        &self.resets
+    }
+
+    /// Returns the Gini coefficient of the current balances, i.e. how evenly
+    /// the rewards are spread over the accounts. Ranges from 0 for perfect equality,
+    /// towards 1 as all rewards are held by a single account.
+    /// Returns 0 when there are no accounts or no rewards.
+    pub fn fairness_index(&self) -> f64 {
+        let mut balances: Vec<f64> = self
+            .accumulated
+            .values()
+            .map(|acc| acc.reward.as_nano() as f64)
+            .collect();
+        let total: f64 = balances.iter().sum();
+        if balances.is_empty() || total == 0.0 {
+            return 0.0;
+        }
+        balances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let count = balances.len() as f64;
+        let weighted: f64 = balances
+            .iter()
+            .enumerate()
+            .map(|(i, balance)| (i + 1) as f64 * balance)
+            .sum();
+        (2.0 * weighted) / (count * total) - (count + 1.0) / count
+    }
+
+    /// Returns the ids already rewarded, in the compact
+    /// encoding of `compact::encode_ids`, for persisting.
+    pub fn compact_idempotency(&self) -> Vec<u8> {
+        encode_ids(&self.idempotency)
+    }
+
+    /// Returns the ids already rewarded, sorted, e.g. for migrating
+    /// them separately from the balances, see `import_idempotency`.
+    /// Ids kept in an idempotency filter cannot be listed, and are not included.
+    pub fn export_idempotency(&self) -> Vec<Id> {
+        let mut ids: Vec<_> = self.idempotency.iter().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Returns a statement of the current balance and lifetime activity
+    /// of the account, or None if the account has never been seen.
+    pub fn statement(&self, account: &AccountId) -> Option<AccountStatement> {
+        let current = self.accumulated.get(account);
+        let history = self.history.get(account);
+        if current.is_none() && history.is_none() {
+            return None;
+        }
+        let current = current.cloned().unwrap_or_default();
+        let history = history.cloned().unwrap_or_default();
+        Some(AccountStatement {
+            account: *account,
+            balance: current.reward,
+            work: current.work,
+            lifetime_earned: history.earned,
+            lifetime_claimed: history.claimed,
+            rewards: history.rewards,
+            contributions: history
+                .contributions
+                .into_iter()
+                .map(|(id, amount, _)| (id, amount))
+                .collect(),
+        })
+    }
+
+    /// Returns the sum of the balances of all accounts,
+    /// i.e. the rewards accumulated but not yet claimed.
+    /// Returns `Error::ExcessiveValue` if the sum overflows.
+    pub fn total_accumulated(&self) -> Result<Money> {
+        sum(self.accumulated.values().map(|acc| &acc.reward)).ok_or(Error::ExcessiveValue)
+    }
+
+    /// Returns the total claimed so far by all accounts, saturating at the max value.
+    pub fn total_claimed(&self) -> Money {
+        self.schedule.claimed()
+    }
+
+    /// Checks that the outstanding balances, escrowed rewards and claimed
+    /// rewards together sum up to the expected amount minted as rewards,
+    /// i.e. that no money was created or destroyed. Balances dropped by
+    /// closing accounts are no longer outstanding, and so must be deducted
+    /// from the expected amount. Returns `Error::NetworkOther` describing the
+    /// difference if it does not balance, and `Error::ExcessiveValue` on overflow.
+    pub fn verify_invariant(&self, expected_minted: Money) -> Result<()> {
+        let outstanding = self
+            .total_accumulated()?
+            .checked_add(sum(self.escrowed.values()).ok_or(Error::ExcessiveValue)?)
+            .ok_or(Error::ExcessiveValue)?;
+        let total = outstanding
+            .checked_add(self.total_claimed())
+            .ok_or(Error::ExcessiveValue)?;
+        if total != expected_minted {
+            return Err(Error::NetworkOther(format!(
+                "Expected {} nanos minted, but {} are outstanding and {} claimed.",
+                expected_minted.as_nano(),
+                outstanding.as_nano(),
+                self.total_claimed().as_nano()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the rewarded ids and accumulated rewards, see `AccumulationSnapshot`.
+    pub fn snapshot(&self) -> AccumulationSnapshot {
+        AccumulationSnapshot {
+            rewarded: self.rewarded_order.iter().cloned().collect(),
+            accumulated: self.accumulated.clone(),
+            next_seq: self.next_seq,
+            store_costs: self.store_costs.clone(),
+            escrowed: self.escrowed.clone(),
+            joined: self.joined.clone(),
+            epoch: self.epoch,
+            refunded_claims: self.refunded_claims.clone(),
+        }
+    }
+
+    /// Returns the accounts with a balance of at least the given minimum,
+    /// highest balance first, e.g. for claiming only balances worth settling.
+    pub fn accounts_at_least(&self, min: Money) -> Vec<(AccountId, Money)> {
+        let mut accounts: Vec<_> = self
+            .accumulated
+            .iter()
+            .filter(|(_, acc)| acc.reward >= min)
+            .map(|(id, acc)| (*id, acc.reward))
+            .collect();
+        accounts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        accounts
+    }
+
+    /// Returns the reward schedule, with the total claimed so far.
+    pub fn reward_schedule(&self) -> &RewardSchedule {
+        &self.schedule
+    }
+
+    /// Returns how rewards overflowing a balance are handled.
+    pub fn overflow_mode(&self) -> OverflowMode {
+        self.overflow_mode
+    }
+
+    /// Returns whether the id has been rewarded.
+    /// With an idempotency filter, this may falsely be true for an id never rewarded.
+    pub fn is_rewarded(&self, id: &Id) -> bool {
+        match &self.idempotency_filter {
+            Some(filter) => filter.contains(id),
+            None => self.idempotency.contains(id),
+        }
+    }
+
+    /// The number of rewarded ids, including those held in an idempotency filter.
+    fn rewarded_count(&self) -> usize {
+        self.idempotency.len().saturating_add(self.filtered_ids)
+    }
+
+    /// Returns the sequence number of the next event, i.e. the number of events applied.
+    /// Events produced by the cmds carry it, and `apply` only accepts the event carrying it.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Returns the current epoch, starting at zero and incremented by `advance_epoch`.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the rewards of the account held in escrow, see `with_escrow_until_age`.
+    pub fn escrowed(&self, account: &AccountId) -> Money {
+        self.escrowed
+            .get(account)
+            .copied()
+            .unwrap_or_else(Money::zero)
+    }
+
+    /// Returns the rewards accumulated by the account in the current epoch.
+    pub fn epoch_rewards(&self, account: &AccountId) -> Money {
+        self.epoch_rewards
+            .get(account)
+            .copied()
+            .unwrap_or_else(Money::zero)
+    }
+
+    /// Returns the rewarded ids for which no distribution has been recorded
+    /// in the account history of this instance, sorted. Such ids were
+    /// for example passed in at construction, when migrating state.
+    /// Ids held in an idempotency filter cannot be listed, so are not included.
+    pub fn orphaned_ids(&self) -> Vec<Id> {
+        let distributed: HashSet<&Id> = self
+            .history
+            .values()
+            .flat_map(|history| history.contributions.iter().map(|(id, _, _)| id))
+            .collect();
+        let mut orphans: Vec<Id> = self
+            .idempotency
+            .iter()
+            .filter(|id| !distributed.contains(id))
+            .cloned()
+            .collect();
+        orphans.sort();
+        orphans
+    }
+
+    /// Returns the reward id of the last applied accumulation,
+    /// or 0 if there has been none.
+    pub fn last_reward_id(&self) -> u64 {
+        self.last_reward_id
+    }
+
+    /// Returns the number of updates applied to the account,
+    /// including its removal, or 0 if it has never been seen.
+    pub fn generation(&self, account: &AccountId) -> u64 {
+        self.generations.get(account).copied().unwrap_or(0)
+    }
+
+    /// Returns a read-only, versioned view of the state, shaped for serving as JSON.
+    pub fn api_snapshot(&self) -> ApiSnapshot {
+        let mut accounts: Vec<ApiAccount> = self
+            .accumulated
+            .iter()
+            .map(|(id, acc)| ApiAccount {
+                id: *id,
+                reward: acc.reward,
+                work: acc.work,
+            })
+            .collect();
+        accounts.sort_by_key(|acc| acc.id);
+        let accumulated = accounts
+            .iter()
+            .fold(Money::zero(), |sum, acc| saturating_add(sum, acc.reward));
+        ApiSnapshot {
+            version: API_SNAPSHOT_VERSION,
+            totals: ApiTotals {
+                accounts: accounts.len() as u64,
+                accumulated,
+                rewarded_ids: self.rewarded_count() as u64,
+            },
+            accounts,
+        }
+    }
+
+    /// Returns the rewards accumulated by the account over the last
+    /// number of epochs, including the current one. At most the current
+    /// and `RETAINED_EPOCHS` past epochs are counted.
+    pub fn reward_velocity(&self, account: &AccountId, epochs: usize) -> Money {
+        if epochs == 0 {
+            return Money::zero();
+        }
+        self.past_epochs
+            .iter()
+            .take(epochs - 1)
+            .filter_map(|epoch| epoch.get(account))
+            .fold(self.epoch_rewards(account), |sum, amount| {
+                saturating_add(sum, *amount)
+            })
+    }
+
+    /// Scans the entire state for inconsistencies, using checked arithmetic throughout.
+    /// This is expensive, as every account, rewarded id and history entry is visited.
+    pub fn audit(&self) -> AuditReport {
+        let mut over_cap: Vec<_> = match self.epoch_cap {
+            None => vec![],
+            Some(cap) => self
+                .epoch_rewards
+                .iter()
+                .filter(|(_, rewards)| **rewards > cap)
+                .map(|(id, _)| *id)
+                .collect(),
+        };
+        over_cap.sort();
+        let mut unrecorded_ids: Vec<_> = self
+            .history
+            .values()
+            .flat_map(|history| history.contributions.iter())
+            .filter(|(id, _, position)| *position >= self.pruned_ids && !self.is_rewarded(id))
+            .map(|(id, _, _)| id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        unrecorded_ids.sort();
+        let mut rewarded_buffered_ids: Vec<_> = self
+            .buffered
+            .keys()
+            .filter(|id| self.is_rewarded(*id))
+            .cloned()
+            .collect();
+        rewarded_buffered_ids.sort();
+        let mut over_max_balance: Vec<_> = match self.max_balance {
+            None => vec![],
+            Some(max) => self
+                .accumulated
+                .iter()
+                .filter(|(_, acc)| acc.reward > max)
+                .map(|(id, _)| *id)
+                .collect(),
+        };
+        over_max_balance.sort();
+        AuditReport {
+            total: sum(self.accumulated.values().map(|a| &a.reward)),
+            total_escrowed: sum(self.escrowed.values()),
+            over_cap,
+            unrecorded_ids,
+            rewarded_buffered_ids,
+            over_max_balance,
+            pruned_ids: self.pruned_ids,
+        }
+    }
+
+    /// Returns a summary of the state, computed in a single pass over the accounts.
+    pub fn metrics(&self) -> AccumulationMetrics {
+        let mut total = 0u128;
+        let mut max = None;
+        let mut min = None;
+        for acc in self.accumulated.values() {
+            total += acc.reward.as_nano() as u128;
+            max = max.max(Some(acc.reward));
+            min = Some(min.map_or(acc.reward, |m: Money| m.min(acc.reward)));
+        }
+        let accounts = self.accumulated.len();
+        let mean = if accounts == 0 {
+            0
+        } else {
+            (total / accounts as u128) as u64
+        };
+        AccumulationMetrics {
+            accounts,
+            total: if total > u64::MAX as u128 {
+                None
+            } else {
+                Some(Money::from_nano(total as u64))
+            },
+            rewarded_ids: self.rewarded_count(),
+            max_balance: max.unwrap_or_else(Money::zero),
+            min_balance: min.unwrap_or_else(Money::zero),
+            mean_balance: Money::from_nano(mean),
+        }
+    }
+
+    /// Returns all accumulated rewards, ordered by account id,
+    /// so that equal states give equal output regardless of the order of insertion.
+    pub fn ordered(&self) -> Vec<(&AccountId, &RewardCounter)> {
+        let mut entries: Vec<_> = self.accumulated.iter().collect();
+        entries.sort_by_key(|(id, _)| **id);
+        entries
+    }
+
+    /// Returns up to `limit` accounts in order of account id, starting after the
+    /// given account, for walking all accounts in pages. The last account
+    /// of a page is the cursor for the next one; a short page is the last one.
+    pub fn page(
+        &self,
+        start_after: Option<&AccountId>,
+        limit: usize,
+    ) -> Vec<(AccountId, RewardCounter)> {
+        let mut entries: Vec<_> = self
+            .accumulated
+            .iter()
+            .filter(|(id, _)| match start_after {
+                None => true,
+                Some(cursor) => *id > cursor,
+            })
+            .map(|(id, acc)| (*id, acc.clone()))
+            .collect();
+        entries.sort_by_key(|(id, _)| *id);
+        entries.truncate(limit);
+        entries
+    }
+
+    /// Returns a hash of the accumulated rewards and the rewarded ids,
+    /// for cheaply comparing the state between nodes. Equal states hash
+    /// equally, regardless of the order in which entries were inserted.
+    ///
+    /// The hash is Sha3-256 over the accounts ordered by id, each as its
+    /// length prefixed bincode serialization, followed by the reward in nanos
+    /// and the work. Then, likewise, the store costs, escrowed rewards and join epochs
+    /// of the accounts, the current epoch, and the refunded claims, each as the account
+    /// followed by the seq of the claim. Lastly the rewarded ids ordered by value,
+    /// each length prefixed, followed by the bits of the idempotency filter, if any.
+    /// All lists are prefixed by their count, and all integers are fixed-width little endian u64. Returns an error if an account can't be serialized.
+    pub fn state_hash(&self) -> Result<Digest> {
+        let mut hasher = Sha3::v256();
+        let accounts = self.ordered();
+        hasher.update(&(accounts.len() as u64).to_le_bytes());
+        for (id, acc) in accounts {
+            let id = bincode::serialize(id).map_err(|e| Error::FailedToParse(e.to_string()))?;
+            hasher.update(&(id.len() as u64).to_le_bytes());
+            hasher.update(&id);
+            hasher.update(&acc.reward.as_nano().to_le_bytes());
+            hasher.update(&acc.work.to_le_bytes());
+        }
+        let nanos = |map: &HashMap<AccountId, Money>| -> Vec<(AccountId, u64)> {
+            map.iter()
+                .map(|(id, amount)| (*id, amount.as_nano()))
+                .collect()
+        };
+        hash_by_account(&mut hasher, nanos(&self.store_costs))?;
+        hash_by_account(&mut hasher, nanos(&self.escrowed))?;
+        hash_by_account(
+            &mut hasher,
+            self.joined
+                .iter()
+                .map(|(id, epoch)| (*id, *epoch))
+                .collect(),
+        )?;
+        hasher.update(&self.epoch.to_le_bytes());
+        hash_by_account(&mut hasher, self.refunded_claims.iter().copied().collect())?;
+        let mut ids: Vec<_> = self.idempotency.iter().collect();
+        ids.sort();
+        hasher.update(&(ids.len() as u64).to_le_bytes());
+        for id in ids {
+            hasher.update(&(id.len() as u64).to_le_bytes());
+            hasher.update(id);
+        }
+        if let Some(filter) = &self.idempotency_filter {
+            for word in filter.words() {
+                hasher.update(&word.to_le_bytes());
+            }
+        }
+        let mut hash = [0; 32];
+        hasher.finalize(&mut hash);
+        Ok(hash)
+    }
+
+    ///
+    pub fn get_all(&self) -> &HashMap<AccountId, RewardCounter> {
+        &self.accumulated
+    }
+
+    /// Checks that the log applies cleanly from empty state, by applying it
+    /// to a scratch instance, as with `from_events`. Returns the first error from applying an event.
+    pub fn validate_log(events: &[AccumulationEvent]) -> Result<()> {
+        Self::check_log(Self::new(Default::default(), Default::default()), events)
+    }
+
+    /// Like `validate_log`, but also returns `Error::InvalidOperation` if a reward or
+    /// a forfeited balance raises a balance above the max, see `with_max_balance`.
+    /// Since the log is checked without the escrow policy, escrowed rewards count as balance.
+    pub fn validate_log_with_max_balance(events: &[AccumulationEvent], max: Money) -> Result<()> {
+        let scratch = Self::new(Default::default(), Default::default()).with_max_balance(max);
+        Self::check_log(scratch, events)
+    }
+
+    fn check_log(mut scratch: Accumulation, events: &[AccumulationEvent]) -> Result<()> {
+        for event in events {
+            scratch.apply(event.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Validates the event against the current state, without applying it,
+    /// e.g. before committing it to a persistent log. The checks are those of
+    /// the cmd producing the event: an account must not be added twice, an id must not
+    /// be rewarded twice nor overflow a balance, and a claim must not exceed the balance.
+    /// Accounts and rewards must be of the current epoch (`Error::InvalidOperation`).
+    /// Lastly, the event must carry the next sequence number, see `apply`.
+    pub fn validate(&self, event: &AccumulationEvent) -> Result<()> {
+        use AccumulationEvent::*;
+        match event {
+            AccountAdded(e) if e.epoch != self.epoch => Err(Error::InvalidOperation),
+            AccountAdded(e) => self.add_account(e.id, e.work).map(|_| ()),
+            RewardsAccumulated(e) => {
+                if e.epoch != self.epoch {
+                    return Err(Error::InvalidOperation);
+                }
+                if e.coalesced.iter().any(|id| self.is_rewarded(id)) {
+                    return Err(Error::DataExists);
+                }
+                if let Some(fingerprint) = &e.fingerprint {
+                    self.check_fingerprint(&e.distribution, fingerprint)?;
+                }
+                self.validate_accumulation(e.id.clone(), e.distribution.clone())
+                    .map(|_| ())
+            }
+            RewardsClaimed(e) => match e.kind {
+                None => self.claim_amount(e.account, e.rewards.reward).map(|_| ()),
+                Some(kind) => {
+                    let balance = self.balance_of(&e.account, kind).ok_or(Error::NoSuchKey)?;
+                    if e.rewards.reward > balance {
+                        return Err(Error::ExcessiveValue);
+                    }
+                    if e.rewards.reward == Money::zero() {
+                        return Err(Error::InsufficientBalance);
+                    }
+                    self.check_min_claim(e.rewards.reward)
+                }
+            },
+            RewardsTransferred(e) => self.transfer(e.from, e.to).map(|_| ()),
+            AccountClosed(e) => {
+                let closed = self.close_account(e.account, e.forfeit_to)?;
+                if closed.forfeited != e.forfeited {
+                    return Err(Error::InvalidOperation);
+                }
+                Ok(())
+            }
+            WorkDecayed(e) => self.decay_work(e.numerator, e.denominator).map(|_| ()),
+            ClaimRefunded(e) => {
+                self.check_refund(e.account, e.claim, e.rewards.reward)?;
+                self.refunded(e.account, &e.rewards).map(|_| ())
+            }
+            EscrowReleased(e) => self.released(e).map(|_| ()),
+        }?;
+        self.check_seq(event)
+    }
+
+    /// -----------------------------------------------------------------
+    /// ---------------------- Cmds -------------------------------------
+    /// -----------------------------------------------------------------
+
+    pub fn add_account(&self, id: AccountId, work: Work) -> Result<AccountAdded> {
+        if self.accumulated.contains_key(&id) {
+            return Err(Error::BalanceExists);
+        }
+        self.check_account_limit(if self.escrowed.contains_key(&id) {
+            0
+        } else {
+            1
+        })?;
+        Ok(AccountAdded {
+            id,
+            work,
+            epoch: self.epoch,
+            seq: self.next_seq,
+        })
+    }
+
+    ///
+    pub fn accumulate(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+    ) -> Result<RewardsAccumulated> {
+        let distribution = self.inflate(distribution)?;
+        self.validate_accumulation(id, distribution)
+    }
+
+    /// Like `accumulate`, but of rewards of the kind,
+    /// which are accumulated and claimable separately, see `balance_of`.
+    /// Rewards accumulated by `accumulate` are farming rewards.
+    pub fn accumulate_kind(
+        &self,
+        id: Id,
+        kind: RewardKind,
+        distribution: HashMap<AccountId, Money>,
+    ) -> Result<RewardsAccumulated> {
+        let mut e = self.accumulate(id, distribution)?;
+        e.kind = kind;
+        Ok(e)
+    }
+
+    /// Scales the amounts by the inflation factor, if any.
+    fn inflate(
+        &self,
+        distribution: HashMap<AccountId, Money>,
+    ) -> Result<HashMap<AccountId, Money>> {
+        let factor = match self.inflation_factor {
+            None => return Ok(distribution),
+            Some(factor) if factor.is_finite() && factor >= 0.0 => factor,
+            Some(_) => return Err(Error::InvalidOperation),
+        };
+        // Fixed point, with nine decimals, to keep amounts exact
+        // beyond the precision of f64.
+        let factor = (factor * 1_000_000_000.0).round() as u128;
+        distribution
+            .into_iter()
+            .map(|(id, amount)| {
+                let scaled = u128::from(amount.as_nano())
+                    .checked_mul(factor)
+                    .map(|scaled| scaled / 1_000_000_000)
+                    .filter(|scaled| *scaled <= u128::from(u64::MAX))
+                    .ok_or(Error::ExcessiveValue)?;
+                Ok((id, Money::from_nano(scaled as u64)))
+            })
+            .collect()
+    }
+
+    fn validate_accumulation(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+    ) -> Result<RewardsAccumulated> {
+        if self.is_rewarded(&id) {
+            return Err(Error::DataExists);
+        }
+        if distribution.is_empty() || distribution.values().any(|amount| *amount == Money::zero()) {
+            return Err(Error::InvalidOperation);
+        }
+        if self.require_registered
+            && distribution
+                .keys()
+                .any(|id| !self.accumulated.contains_key(id))
+        {
+            return Err(Error::NoSuchKey);
+        }
+        let credited = self.credited(&distribution, self.epoch)?;
+        for accumulated in credited.values() {
+            self.check_max_balance(accumulated.reward)?;
+        }
+        // recipients of escrowed rewards count as accounts already
+        let new_accounts = distribution
+            .keys()
+            .filter(|id| !self.accumulated.contains_key(id) && !self.escrowed.contains_key(id))
+            .count();
+        self.check_account_limit(new_accounts)?;
+        if let Some(cap) = self.epoch_cap {
+            for (id, amount) in &distribution {
+                match self.epoch_rewards(id).checked_add(*amount) {
+                    Some(epoch_rewards) if epoch_rewards <= cap => (),
+                    _ => return Err(Error::InvalidOperation),
+                }
+            }
+        }
+        if let Some(pool) = self.pool {
+            let total = sum(distribution.values()).ok_or(Error::ExcessiveValue)?;
+            if total > pool {
+                return Err(Error::InsufficientBalance);
+            }
+        }
+
+        Ok(RewardsAccumulated {
+            id,
+            distribution,
+            fingerprint: None,
+            reward_id: self.last_reward_id + 1,
+            coalesced: vec![],
+            kind: RewardKind::Farming,
+            epoch: self.epoch,
+            seq: self.next_seq,
+        })
+    }
+
+    fn check_max_balance(&self, balance: Money) -> Result<()> {
+        match self.max_balance {
+            Some(max) if balance > max => Err(Error::InvalidOperation),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_min_claim(&self, amount: Money) -> Result<()> {
+        match self.min_claim {
+            Some(min) if amount < min => Err(Error::InsufficientBalance),
+            _ => Ok(()),
+        }
+    }
+
+    /// The number of accounts, including recipients with only escrowed rewards.
+    fn account_count(&self) -> usize {
+        let escrowed_only = self
+            .escrowed
+            .keys()
+            .filter(|id| !self.accumulated.contains_key(id))
+            .count();
+        self.accumulated.len() + escrowed_only
+    }
+
+    fn check_account_limit(&self, new_accounts: usize) -> Result<()> {
+        match self.max_accounts {
+            Some(max) if self.account_count().saturating_add(new_accounts) > max => {
+                Err(Error::TooManyEntries)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `accumulate`, but also rejects with `Error::DataExists`
+    /// if the fingerprint was already rewarded to any of the accounts,
+    /// so that the same logical work is not rewarded twice under different ids.
+    pub fn accumulate_unique(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+        fingerprint: Fingerprint,
+    ) -> Result<RewardsAccumulated> {
+        self.check_fingerprint(&distribution, &fingerprint)?;
+        let mut e = self.accumulate(id, distribution)?;
+        e.fingerprint = Some(fingerprint);
+        Ok(e)
+    }
+
+    /// Returns `Error::DataExists` if the fingerprint was already rewarded to any of the accounts.
+    /// Validation and `apply` share this, so that they reject the same rewards.
+    fn check_fingerprint(
+        &self,
+        distribution: &HashMap<AccountId, Money>,
+        fingerprint: &Fingerprint,
+    ) -> Result<()> {
+        for account in distribution.keys() {
+            if let Some(rewarded) = self.fingerprints.get(account) {
+                if rewarded.contains_key(fingerprint) {
+                    return Err(Error::DataExists);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-credits the rewards of a claim whose settlement failed, reverting the claim,
+    /// and re-adding the account with its work at the time of the claim if the claim removed it.
+    /// The refund is not an accumulation: no policy (such as inflation, the pool or caps)
+    /// applies, the work is left as it is, and the claimed totals are reduced by the refund.
+    /// The refunded claims are recorded by account and sequence number, separately from
+    /// the rewarded ids and never pruned, so each claim can only be refunded once.
+    /// Returns `Error::DataExists` if already refunded, `Error::InvalidOperation` if the claim
+    /// is not yet applied or the refund exceeds the claimed totals, and `Error::ExcessiveValue`
+    /// if the refund would overflow the balance.
+    pub fn refund_claim(&self, claim: &RewardsClaimed) -> Result<ClaimRefunded> {
+        self.check_refund(claim.account, claim.seq, claim.rewards.reward)?;
+        let _ = self.refunded(claim.account, &claim.rewards)?;
+        Ok(ClaimRefunded {
+            claim: claim.seq,
+            account: claim.account,
+            rewards: claim.rewards.clone(),
+            kind: claim.kind,
+            seq: self.next_seq,
+        })
+    }
+
+    /// Returns `Error::DataExists` if the claim is already refunded, and `Error::InvalidOperation`
+    /// if it is not yet applied, or the refund exceeds what was claimed from the account.
+    fn check_refund(&self, account: AccountId, claim: u64, reward: Money) -> Result<()> {
+        if self.refunded_claims.contains(&(account, claim)) {
+            return Err(Error::DataExists);
+        }
+        let claimed = self
+            .history
+            .get(&account)
+            .map(|history| history.claimed)
+            .unwrap_or_default();
+        if claim >= self.next_seq || reward > claimed || reward > self.schedule.claimed() {
+            return Err(Error::InvalidOperation);
+        }
+        Ok(())
+    }
+
+    /// Returns the rewards of the account with the refund credited back.
+    fn refunded(&self, account: AccountId, refund: &RewardCounter) -> Result<RewardCounter> {
+        match self.accumulated.get(&account) {
+            None => Ok(refund.clone()),
+            Some(existing) => Ok(RewardCounter {
+                reward: existing
+                    .reward
+                    .checked_add(refund.reward)
+                    .ok_or(Error::ExcessiveValue)?,
+                work: existing.work,
+            }),
+        }
+    }
+
+    /// Coalesces all buffered rewards into a single accumulation, under an id
+    /// derived from the buffered ids. Applying it rewards the buffered ids,
+    /// and removes them from the buffer. The work of each recipient is
+    /// incremented once for the coalesced accumulation, not once per buffered reward.
+    /// Returns `Error::NoSuchData` if nothing is buffered.
+    pub fn flush_accumulated(&self) -> Result<RewardsAccumulated> {
+        if self.buffered.is_empty() {
+            return Err(Error::NoSuchData);
+        }
+        let mut deltas: HashMap<AccountId, Money> = HashMap::new();
+        for distribution in self.buffered.values() {
+            for (account, amount) in distribution {
+                let delta = deltas.entry(*account).or_insert_with(Money::zero);
+                *delta = delta.checked_add(*amount).ok_or(Error::ExcessiveValue)?;
+            }
+        }
+        let mut coalesced: Vec<Id> = self.buffered.keys().cloned().collect();
+        coalesced.sort();
+        let mut hasher = Sha3::v256();
+        hasher.update(&encode_ids(&coalesced));
+        let mut hash = [0; 32];
+        hasher.finalize(&mut hash);
+        let mut id = b"flush".to_vec();
+        id.extend(&hash);
+
+        // the buffered amounts are already inflated
+        let mut e = self.validate_accumulation(id, deltas)?;
+        e.coalesced = coalesced;
+        Ok(e)
+    }
+
+    /// Validates a batch of accumulations as a unit, i.e. either
+    /// an event is produced for every entry, or the whole batch is rejected.
+    /// An id occurring more than once in the batch rejects it with `Error::DataExists`,
+    /// unless `dedupe` is set, in which case the first occurrence wins, and
+    /// the ids of the skipped later occurrences are returned along with the events.
+    /// Every entry is validated on top of the preceding ones, so that entries
+    /// which are valid one by one, but together would e.g. overflow a balance, are rejected.
+    pub fn accumulate_batch(
+        &self,
+        entries: Vec<(Id, HashMap<AccountId, Money>)>,
+        dedupe: bool,
+    ) -> Result<(Vec<RewardsAccumulated>, Vec<Id>)> {
+        let mut candidate = self.clone();
+        let mut batch_ids = HashSet::new();
+        let mut events = vec![];
+        let mut skipped = vec![];
+        for (id, distribution) in entries {
+            if batch_ids.contains(&id) {
+                if dedupe {
+                    skipped.push(id);
+                    continue;
+                }
+                return Err(Error::DataExists);
+            }
+            let _ = batch_ids.insert(id.clone());
+            let e = candidate.accumulate(id, distribution)?;
+            candidate.apply(AccumulationEvent::RewardsAccumulated(e.clone()))?;
+            events.push(e);
+        }
+        Ok((events, skipped))
+    }
+
+    /// Like `accumulate`, but the distribution is given as entries with proofs
+    /// of their eligibility, against the Merkle root of all eligible entries.
+    /// Rejects with `Error::InvalidOperation` if any proof does not verify,
+    /// or if an account occurs more than once.
+    pub fn accumulate_proven(
+        &self,
+        id: Id,
+        root: Digest,
+        entries: Vec<(AccountId, Money, MerkleProof)>,
+    ) -> Result<RewardsAccumulated> {
+        let mut distribution = HashMap::new();
+        for (account, amount, proof) in entries {
+            if !proof.verify(leaf(&account, amount)?, root) {
+                return Err(Error::InvalidOperation);
+            }
+            if distribution.insert(account, amount).is_some() {
+                return Err(Error::InvalidOperation);
+            }
+        }
+        self.accumulate(id, distribution)
+    }
+
+    /// Like `accumulate`, but also returns a claim for every account in the distribution
+    /// whose balance would reach the auto claim threshold. The claims are produced by `claim`,
+    /// on top of the applied accumulation, so rewards held in escrow are not claimed, and
+    /// balances below the minimum claim are kept (see `with_min_claim`). The claims are to be
+    /// applied after the accumulation, in the returned order, that of their sequence numbers.
+    /// As with any cmd, nothing may be applied in between, so on a `SharedAccumulation`
+    /// the events are to be produced and applied under the same write lock.
+    /// Without a configured threshold, no claims are returned.
+    pub fn accumulate_with_auto_claim(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+    ) -> Result<(RewardsAccumulated, Vec<RewardsClaimed>)> {
+        let accumulated = self.accumulate(id, distribution)?;
+        let threshold = match self.auto_claim_threshold {
+            None => return Ok((accumulated, vec![])),
+            Some(threshold) => threshold,
+        };
+        let mut candidate = self.clone();
+        candidate.apply(AccumulationEvent::RewardsAccumulated(accumulated.clone()))?;
+        let mut claims = vec![];
+        for account in accumulated.distribution.keys() {
+            match candidate.get(account) {
+                Some(rewards) if rewards.reward >= threshold => (),
+                _ => continue,
+            }
+            let claim = match candidate.claim(*account) {
+                Err(Error::InsufficientBalance) => continue,
+                claim => claim?,
+            };
+            candidate.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+            claims.push(claim);
+        }
+        Ok((accumulated, claims))
+    }
+
+    /// Like `accumulate`, but when the accumulation is valid, the total of the
+    /// distribution is first minted, and any error from minting fails the accumulation.
+    pub fn accumulate_minted(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+        mint: &impl Mint,
+    ) -> Result<RewardsAccumulated> {
+        let e = self.accumulate(id, distribution)?;
+        let total = sum(e.distribution.values()).ok_or(Error::ExcessiveValue)?;
+        mint.mint(total)?;
+        Ok(e)
+    }
+
+    /// Like `accumulate`, but the accumulated distribution, i.e. after any inflation,
+    /// must sum to exactly the amount of the mint receipt, or `Error::InvalidOperation` is returned.
+    pub fn accumulate_with_receipt(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+        receipt: MintReceipt,
+    ) -> Result<RewardsAccumulated> {
+        let e = self.accumulate(id, distribution)?;
+        let total = sum(e.distribution.values()).ok_or(Error::ExcessiveValue)?;
+        if total != receipt.amount {
+            return Err(Error::InvalidOperation);
+        }
+        Ok(e)
+    }
+
+    /// Accumulates the total split by the shares, given in basis points,
+    /// which must sum to 10 000 (i.e. 100 %), or `Error::InvalidOperation` is returned.
+    /// Each account gets its share rounded down, and the nanos left by the rounding
+    /// go one each to the accounts with the largest rounded off fractions
+    /// (ties broken by account id), so that the amounts sum to exactly the total.
+    /// Returns `Error::InvalidOperation` if the accumulated distribution, i.e. after
+    /// any inflation, would not sum to exactly the total.
+    pub fn accumulate_percentages(
+        &self,
+        id: Id,
+        total: Money,
+        shares: Vec<(AccountId, u16)>,
+    ) -> Result<RewardsAccumulated> {
+        const WHOLE: u128 = 10_000;
+        let mut basis_points: HashMap<AccountId, u128> = HashMap::new();
+        for (account, points) in shares {
+            *basis_points.entry(account).or_insert(0) += u128::from(points);
+        }
+        if basis_points.values().sum::<u128>() != WHOLE {
+            return Err(Error::InvalidOperation);
+        }
+
+        let total = u128::from(total.as_nano());
+        // (account, rounded down amount, rounded off fraction)
+        let mut amounts: Vec<(AccountId, u128, u128)> = basis_points
+            .into_iter()
+            .map(|(account, points)| {
+                let exact = total * points;
+                (account, exact / WHOLE, exact % WHOLE)
+            })
+            .collect();
+        let mut remainder = total - amounts.iter().map(|(_, amount, _)| amount).sum::<u128>();
+        amounts.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        for (_, amount, _) in amounts.iter_mut() {
+            if remainder == 0 {
+                break;
+            }
+            *amount += 1;
+            remainder -= 1;
+        }
+
+        let distribution = amounts
+            .into_iter()
+            .filter(|(_, amount, _)| *amount > 0)
+            .map(|(account, amount, _)| (account, Money::from_nano(amount as u64)))
+            .collect();
+        let e = self.accumulate(id, distribution)?;
+        if sum(e.distribution.values()) != Some(Money::from_nano(total as u64)) {
+            return Err(Error::InvalidOperation);
+        }
+        Ok(e)
+    }
+
+    /// Like `accumulate`, but recipients that are not yet registered are
+    /// resolved by the caller; those the resolver returns work for, are added with
+    /// that work, and any recipient it rejects fails the accumulation with `Error::NoSuchKey`.
+    /// The `AccountAdded` events are to be applied before the accumulation.
+    pub fn accumulate_with_resolver(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+        resolver: impl Fn(&AccountId) -> Option<Work>,
+    ) -> Result<(Vec<AccountAdded>, RewardsAccumulated)> {
+        // the accumulation is validated with the accounts added
+        let mut candidate = self.clone();
+        let mut added = vec![];
+        for account in distribution.keys() {
+            if self.accumulated.contains_key(account) {
+                continue;
+            }
+            match resolver(account) {
+                None => return Err(Error::NoSuchKey),
+                Some(work) => {
+                    let e = candidate.add_account(*account, work)?;
+                    candidate.apply(AccumulationEvent::AccountAdded(e.clone()))?;
+                    added.push(e);
+                }
+            }
+        }
+        let accumulated = candidate.accumulate(id, distribution)?;
+        Ok((added, accumulated))
+    }
+
+    /// Moves the rewards and work of an account to another account,
+    /// such as when the account key is rotated, without claiming them.
+    /// Returns `Error::NoSuchKey` if there is no account to move from,
+    /// and `Error::BalanceExists` if the account to move to already exists.
+    pub fn transfer(&self, from: AccountId, to: AccountId) -> Result<RewardsTransferred> {
+        let rewards = self.get(&from).ok_or(Error::NoSuchKey)?.clone();
+        if self.accumulated.contains_key(&to) {
+            return Err(Error::BalanceExists);
+        }
+        Ok(RewardsTransferred {
+            from,
+            to,
+            rewards,
+            seq: self.next_seq,
+        })
+    }
+
+    /// Removes the account, so that it no longer accumulates rewards.
+    /// Its balance, including its rewards held in escrow, is added to the `forfeit_to`
+    /// account, without counting as work, or dropped if there is none.
+    /// Returns `Error::NoSuchKey` if there is no such account, or if the account to forfeit to
+    /// is not registered while registration is required, `Error::InvalidOperation`
+    /// if forfeiting to the account itself or above its max balance (see `with_max_balance`),
+    /// and `Error::ExcessiveValue` if the forfeited balance would overflow the account forfeited to.
+    pub fn close_account(
+        &self,
+        account: AccountId,
+        forfeit_to: Option<AccountId>,
+    ) -> Result<AccountClosed> {
+        let forfeited = self.forfeitable(&account)?;
+        if let Some(to) = forfeit_to {
+            if to == account {
+                return Err(Error::InvalidOperation);
+            }
+            match self.get(&to) {
+                None if self.require_registered => return Err(Error::NoSuchKey),
+                None => self.check_max_balance(forfeited)?,
+                Some(existing) => {
+                    let balance = existing
+                        .reward
+                        .checked_add(forfeited)
+                        .ok_or(Error::ExcessiveValue)?;
+                    self.check_max_balance(balance)?;
+                }
+            }
+        }
+        Ok(AccountClosed {
+            account,
+            forfeited,
+            forfeit_to,
+            seq: self.next_seq,
+        })
+    }
+
+    /// Returns the balance and escrowed rewards of the account, forfeited when closing it.
+    fn forfeitable(&self, account: &AccountId) -> Result<Money> {
+        self.get(account)
+            .ok_or(Error::NoSuchKey)?
+            .reward
+            .checked_add(self.escrowed(account))
+            .ok_or(Error::ExcessiveValue)
+    }
+
+    /// Scales down the work of every account by the fraction `numerator / denominator`,
+    /// rounded down, so that accounts which stop earning lose their standing over time,
+    /// e.g. when called once per epoch. A fraction of a half halves the work, and so on.
+    /// Returns `Error::InvalidOperation` if the denominator is zero, or the fraction exceeds one.
+    pub fn decay_work(&self, numerator: Work, denominator: Work) -> Result<WorkDecayed> {
+        if denominator == 0 || numerator > denominator {
+            return Err(Error::InvalidOperation);
+        }
+        Ok(WorkDecayed {
+            numerator,
+            denominator,
+            seq: self.next_seq,
+        })
+    }
+
+    /// Claims the full balance of the account, see `claim_amount`.
+    pub fn claim(&self, account: AccountId) -> Result<RewardsClaimed> {
+        let balance = self.get(&account).ok_or(Error::NoSuchKey)?.reward;
+        self.claim_amount(account, balance)
+    }
+
+    /// Claims a part of the balance of the account. The event carries the
+    /// claimed amount and the current work of the account. Applying it
+    /// subtracts the amount and leaves the work intact, only removing
+    /// the account once nothing remains.
+    /// Returns `Error::ExcessiveValue` if the amount exceeds the balance,
+    /// and `Error::InsufficientBalance` if it is below the minimum claim, see `with_min_claim`.
+    pub fn claim_amount(&self, account: AccountId, amount: Money) -> Result<RewardsClaimed> {
+        let existing = self.get(&account).ok_or(Error::NoSuchKey)?;
+        if amount > existing.reward {
+            return Err(Error::ExcessiveValue);
+        }
+        self.check_min_claim(amount)?;
+        Ok(RewardsClaimed {
+            account,
+            rewards: RewardCounter {
+                reward: amount,
+                work: existing.work,
+            },
+            destination: account,
+            kind: None,
+            seq: self.next_seq,
+        })
+    }
+
+    /// Claims the full balance of each of the accounts, as a unit, i.e. either
+    /// a claim is produced for every account, or the whole batch is rejected.
+    /// The claims are to be applied in the returned order, that of their sequence numbers.
+    /// Returns `Error::InvalidOperation` if an account occurs more than once,
+    /// and otherwise the first error of claiming any of the accounts.
+    pub fn claim_batch(&self, accounts: Vec<AccountId>) -> Result<Vec<RewardsClaimed>> {
+        let mut seen = HashSet::new();
+        if !accounts.iter().all(|account| seen.insert(*account)) {
+            return Err(Error::InvalidOperation);
+        }
+        let mut claims = vec![];
+        for account in accounts {
+            // claims of distinct accounts are independent of each other
+            let mut claim = self.claim(account)?;
+            claim.seq += claims.len() as u64;
+            claims.push(claim);
+        }
+        Ok(claims)
+    }
+
+    /// Claims the part of the balance rewarded of the kind, see `balance_of`.
+    /// The rest of the balance is left intact.
+    /// Returns `Error::InsufficientBalance` if nothing is rewarded of the kind.
+    pub fn claim_kind(&self, account: AccountId, kind: RewardKind) -> Result<RewardsClaimed> {
+        let balance = self.balance_of(&account, kind).ok_or(Error::NoSuchKey)?;
+        if balance == Money::zero() {
+            return Err(Error::InsufficientBalance);
+        }
+        let mut claim = self.claim_amount(account, balance)?;
+        claim.kind = Some(kind);
+        Ok(claim)
+    }
+
+    /// Claims the full balance of the account, to be paid out to
+    /// the destination (e.g. a cold wallet) instead of to the account.
+    pub fn claim_to(&self, account: AccountId, destination: AccountId) -> Result<RewardsClaimed> {
+        let mut claim = self.claim(account)?;
+        claim.destination = destination;
+        Ok(claim)
+    }
+
+    /// -----------------------------------------------------------------
+    /// ---------------------- Mutation ---------------------------------
+    /// -----------------------------------------------------------------
+
+    /// Mutates state.
+    /// Returns `Error::ExcessiveValue`, leaving the state unchanged,
+    /// if the event would overflow the balance of an account,
+    /// such as when replaying events out of order or from a corrupt log.
+    /// Returns `Error::InvalidOperation` if the sequence number of the event
+    /// is not the next one, i.e. if events are gapped, repeated or reordered,
+    /// or if a reward or forfeited balance raises a balance above the max, see `with_max_balance`.
+    /// Returns `Error::BalanceExists` if the added account exists,
+    /// and `Error::DataExists` if the accumulated id is already rewarded.
+    /// The most recent events applied can be undone, see `undo_last`.
+    pub fn apply(&mut self, event: AccumulationEvent) -> Result<()> {
+        self.check_seq(&event)?;
+        let undo = self.undo_record(&event);
+        let notified = if self.subscribers.0.is_empty() {
+            None
+        } else {
+            Some(event.clone())
+        };
+        self.apply_event(event)?;
+        self.next_seq += 1;
+        if let Some(event) = notified {
+            self.notify(&event);
+        }
+        self.undo_log.push_back(undo);
+        if self.undo_log.len() > RETAINED_UNDO {
+            let _ = self.undo_log.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Registers a callback, invoked after every successful `apply`
+    /// with the applied event. Subscribers are invoked in the order they
+    /// were registered. They must be `Send + Sync`, as the instance may
+    /// be shared between threads, see `SharedAccumulation`. Clones of the
+    /// instance start out without subscribers.
+    pub fn subscribe(&mut self, f: Subscriber) {
+        self.subscribers.0.push(f);
+    }
+
+    fn notify(&mut self, event: &AccumulationEvent) {
+        for subscriber in self.subscribers.0.iter_mut() {
+            subscriber(event);
+        }
+    }
+
+    fn check_seq(&self, event: &AccumulationEvent) -> Result<()> {
+        if event.seq() != self.next_seq {
+            return Err(Error::InvalidOperation);
+        }
+        Ok(())
+    }
+
+    /// Reverts the most recently applied event, restoring the state as it was before.
+    /// At most `RETAINED_UNDO` events can be undone, in reverse order of application.
+    /// Ids dropped by the idempotency cap are not restored.
+    /// Returns `Error::NoSuchData` when there is nothing to undo.
+    pub fn undo_last(&mut self) -> Result<()> {
+        let undo = self.undo_log.pop_back().ok_or(Error::NoSuchData)?;
+        if let Some((epoch, epoch_rewards, past_epochs)) = undo.epochs {
+            self.epoch = epoch;
+            self.epoch_rewards = epoch_rewards;
+            self.past_epochs = past_epochs;
+        }
+        for account in undo.accounts {
+            let id = account.account;
+            restore(&mut self.accumulated, id, account.accumulated);
+            restore(&mut self.escrowed, id, account.escrowed);
+            restore(&mut self.epoch_rewards, id, account.epoch_rewards);
+            restore(&mut self.generations, id, account.generation);
+            restore(&mut self.joined, id, account.joined);
+            restore(&mut self.store_costs, id, account.store_cost);
+            match account.history {
+                None => {
+                    let _ = self.history.remove(&id);
+                }
+                Some((earned, claimed, rewards, oldest)) => {
+                    if let Some(history) = self.history.get_mut(&id) {
+                        if history.rewards > rewards {
+                            let _ = history.contributions.pop_back();
+                            if let Some(oldest) = oldest {
+                                history.contributions.push_front(oldest);
+                            }
+                        }
+                        history.earned = earned;
+                        history.claimed = claimed;
+                        history.rewards = rewards;
+                    }
+                }
+            }
+            if let Some(fingerprint) = account.fingerprint {
+                if let Some(fingerprints) = self.fingerprints.get_mut(&id) {
+                    let _ = fingerprints.remove(&fingerprint);
+                    if fingerprints.is_empty() {
+                        let _ = self.fingerprints.remove(&id);
+                    }
+                }
+            }
+        }
+        self.pool = undo.pool;
+        self.last_reward_id = undo.last_reward_id;
+        let rewarded = undo.rewarded;
+        for id in &rewarded {
+            let _ = self.idempotency.remove(id);
+        }
+        self.rewarded_order.retain(|id| !rewarded.contains(id));
+        if let Some(claim) = undo.refunded_claim {
+            let _ = self.refunded_claims.remove(&claim);
+        }
+        self.buffered.extend(undo.buffered);
+        self.schedule = self.schedule.with_claimed(undo.claimed);
+        self.next_seq -= 1;
+        Ok(())
+    }
+
+    fn undo_record(&self, event: &AccumulationEvent) -> UndoRecord {
+        use AccumulationEvent::*;
+        let (accounts, fingerprint, ids): (Vec<AccountId>, _, Vec<&Id>) = match event {
+            AccountAdded(e) => (vec![e.id], None, vec![]),
+            RewardsAccumulated(e) => (
+                e.distribution.keys().copied().collect(),
+                e.fingerprint.as_ref(),
+                std::iter::once(&e.id).chain(&e.coalesced).collect(),
+            ),
+            RewardsClaimed(e) => (vec![e.account], None, vec![]),
+            RewardsTransferred(e) => (vec![e.from, e.to], None, vec![]),
+            AccountClosed(e) => (
+                std::iter::once(e.account).chain(e.forfeit_to).collect(),
+                None,
+                vec![],
+            ),
+            WorkDecayed(_) => (self.accumulated.keys().copied().collect(), None, vec![]),
+            ClaimRefunded(e) => (vec![e.account], None, vec![]),
+            EscrowReleased(e) => (e.released.keys().copied().collect(), None, vec![]),
+        };
+        let epoch = match event {
+            AccountAdded(e) => e.epoch,
+            RewardsAccumulated(e) => e.epoch,
+            EscrowReleased(e) => e.epoch,
+            _ => self.epoch,
+        };
+        let accounts = accounts
+            .into_iter()
+            .map(|account| AccountUndo {
+                account,
+                accumulated: self.accumulated.get(&account).cloned(),
+                escrowed: self.escrowed.get(&account).copied(),
+                epoch_rewards: self.epoch_rewards.get(&account).copied(),
+                history: self.history.get(&account).map(|history| {
+                    let oldest = Some(&history.contributions)
+                        .filter(|c| c.len() >= RETAINED_CONTRIBUTIONS)
+                        .and_then(|c| c.front().cloned());
+                    (history.earned, history.claimed, history.rewards, oldest)
+                }),
+                generation: self.generations.get(&account).copied(),
+                joined: self.joined.get(&account).copied(),
+                store_cost: self.store_costs.get(&account).copied(),
+                fingerprint: fingerprint
+                    .filter(|fingerprint| {
+                        !matches!(self.fingerprints.get(&account),
+                            Some(existing) if existing.contains_key(*fingerprint))
+                    })
+                    .cloned(),
+            })
+            .collect();
+        let mut rewarded = vec![];
+        let mut buffered = vec![];
+        for id in ids {
+            if !self.idempotency.contains(id) && !rewarded.contains(id) {
+                rewarded.push(id.clone());
+            }
+            if let Some(distribution) = self.buffered.get(id) {
+                buffered.push((id.clone(), distribution.clone()));
+            }
+        }
+        UndoRecord {
+            accounts,
+            pool: self.pool,
+            last_reward_id: self.last_reward_id,
+            rewarded,
+            buffered,
+            claimed: self.schedule.claimed(),
+            epochs: if epoch > self.epoch {
+                Some((
+                    self.epoch,
+                    self.epoch_rewards.clone(),
+                    self.past_epochs.clone(),
+                ))
+            } else {
+                None
+            },
+            refunded_claim: match event {
+                ClaimRefunded(e) => Some((e.account, e.claim)),
+                _ => None,
+            },
+        }
+    }
+
+    fn apply_event(&mut self, event: AccumulationEvent) -> Result<()> {
+        use AccumulationEvent::*;
+        match event {
+            AccountAdded(e) => {
+                if self.accumulated.contains_key(&e.id) {
+                    return Err(Error::BalanceExists);
+                }
+                self.enter_epoch(e.epoch);
+                self.bump_generation(e.id);
+                let _ = self.joined.entry(e.id).or_insert(e.epoch);
+                let _ = self.accumulated.insert(
+                    e.id,
+                    RewardCounter {
+                        reward: Money::zero(),
+                        work: e.work,
+                    },
+                );
+            }
+            RewardsAccumulated(e) => {
+                // check before mutating anything, so that a rejection leaves the state unchanged
+                if std::iter::once(&e.id)
+                    .chain(&e.coalesced)
+                    .any(|id| self.is_rewarded(id))
+                {
+                    return Err(Error::DataExists);
+                }
+                if let Some(fingerprint) = &e.fingerprint {
+                    self.check_fingerprint(&e.distribution, fingerprint)?;
+                }
+                let mut credited = self.credited(&e.distribution, e.epoch)?;
+                for accumulated in credited.values() {
+                    self.check_max_balance(accumulated.reward)?;
+                }
+                self.enter_epoch(e.epoch);
+                self.last_reward_id = self.last_reward_id.max(e.reward_id);
+                if let Some(pool) = self.pool {
+                    // validation shall have ensured the pool covers the total
+                    let total = sum(e.distribution.values()).unwrap_or(pool);
+                    self.pool = Some(pool.checked_sub(total).unwrap_or_else(Money::zero));
+                }
+                // the position the id is recorded at, see `record_rewarded`
+                let position = self.pruned_ids + self.rewarded_order.len() as u64;
+                if let Some(fingerprint) = &e.fingerprint {
+                    for id in e.distribution.keys() {
+                        let _ = self
+                            .fingerprints
+                            .entry(*id)
+                            .or_default()
+                            .insert(fingerprint.clone(), position);
+                    }
+                }
+                for (id, amount) in &e.distribution {
+                    self.bump_generation(*id);
+                    let epoch_rewards = self.epoch_rewards.entry(*id).or_default();
+                    *epoch_rewards = saturating_add(*epoch_rewards, *amount);
+                    let history = self.history.entry(*id).or_default();
+                    history.earned = saturating_add(history.earned, *amount);
+                    history.rewards += 1;
+                    if history.contributions.len() >= RETAINED_CONTRIBUTIONS {
+                        let _ = history.contributions.pop_front();
+                    }
+                    history
+                        .contributions
+                        .push_back((e.id.clone(), *amount, position));
+                }
+                for (id, amount) in e.distribution {
+                    let _ = self.joined.entry(id).or_insert(e.epoch);
+                    match credited.remove(&id) {
+                        Some(accumulated) => {
+                            if e.kind == RewardKind::StoreCost {
+                                let store_cost = self.store_costs.entry(id).or_default();
+                                *store_cost = saturating_add(*store_cost, amount);
+                            }
+                            let _ = self.accumulated.insert(id, accumulated);
+                        }
+                        None => {
+                            let escrowed = self.escrowed.entry(id).or_default();
+                            *escrowed = saturating_add(*escrowed, amount);
+                        }
+                    }
+                }
+                self.record_rewarded(e.id);
+                for id in e.coalesced {
+                    let _ = self.buffered.remove(&id);
+                    self.record_rewarded(id);
+                }
+            }
+            RewardsClaimed(e) => {
+                let remaining = self
+                    .accumulated
+                    .get(&e.account)
+                    .ok_or(Error::NoSuchKey)?
+                    .reward
+                    .checked_sub(e.rewards.reward)
+                    .ok_or(Error::ExcessiveValue)?;
+                self.bump_generation(e.account);
+                let history = self.history.entry(e.account).or_default();
+                history.claimed = saturating_add(history.claimed, e.rewards.reward);
+                let claimed = saturating_add(self.schedule.claimed(), e.rewards.reward);
+                self.schedule = self.schedule.with_claimed(claimed);
+                let remaining = Some(remaining).filter(|remaining| *remaining > Money::zero());
+                match remaining {
+                    Some(remaining) => {
+                        if let Some(existing) = self.accumulated.get_mut(&e.account) {
+                            existing.reward = remaining;
+                        }
+                        // claims regardless of kind are taken from farming rewards first
+                        if let Some(store_cost) = self.store_costs.get_mut(&e.account) {
+                            if e.kind == Some(RewardKind::StoreCost) {
+                                *store_cost =
+                                    store_cost.checked_sub(e.rewards.reward).unwrap_or_default();
+                            }
+                            *store_cost = (*store_cost).min(remaining);
+                        }
+                    }
+                    None => {
+                        let _ = self.accumulated.remove(&e.account);
+                        let _ = self.store_costs.remove(&e.account);
+                    }
+                }
+            }
+            RewardsTransferred(e) => {
+                if self.accumulated.contains_key(&e.to) {
+                    return Err(Error::BalanceExists);
+                }
+                let rewards = self.accumulated.remove(&e.from).ok_or(Error::NoSuchKey)?;
+                let _ = self.accumulated.insert(e.to, rewards);
+                if let Some(store_cost) = self.store_costs.remove(&e.from) {
+                    let _ = self.store_costs.insert(e.to, store_cost);
+                }
+                if let Some(escrowed) = self.escrowed.remove(&e.from) {
+                    let _ = self.escrowed.insert(e.to, escrowed);
+                }
+                if let Some(joined) = self.joined.get(&e.from).copied() {
+                    let _ = self.joined.insert(e.to, joined);
+                }
+                self.bump_generation(e.from);
+                self.bump_generation(e.to);
+            }
+            AccountClosed(e) => {
+                // the forfeited amount is that of the state, not only as claimed by the event
+                if self.forfeitable(&e.account)? != e.forfeited || e.forfeit_to == Some(e.account) {
+                    return Err(Error::InvalidOperation);
+                }
+                let forfeited_to = match e.forfeit_to {
+                    None => None,
+                    Some(to) => {
+                        let mut existing = self.accumulated.get(&to).cloned().unwrap_or_default();
+                        existing.reward = existing
+                            .reward
+                            .checked_add(e.forfeited)
+                            .ok_or(Error::ExcessiveValue)?;
+                        self.check_max_balance(existing.reward)?;
+                        Some((to, existing))
+                    }
+                };
+                let _ = self
+                    .accumulated
+                    .remove(&e.account)
+                    .ok_or(Error::NoSuchKey)?;
+                let _ = self.store_costs.remove(&e.account);
+                let _ = self.escrowed.remove(&e.account);
+                self.bump_generation(e.account);
+                if let Some((to, accumulated)) = forfeited_to {
+                    let _ = self.joined.entry(to).or_insert(self.epoch);
+                    let _ = self.accumulated.insert(to, accumulated);
+                    self.bump_generation(to);
+                }
+            }
+            WorkDecayed(e) => {
+                if e.denominator == 0 || e.numerator > e.denominator {
+                    return Err(Error::InvalidOperation);
+                }
+                let accounts: Vec<_> = self.accumulated.keys().copied().collect();
+                for account in accounts {
+                    if let Some(existing) = self.accumulated.get_mut(&account) {
+                        existing.work = decay(existing.work, e.numerator, e.denominator);
+                    }
+                    self.bump_generation(account);
+                }
+            }
+            ClaimRefunded(e) => {
+                self.check_refund(e.account, e.claim, e.rewards.reward)?;
+                let refunded = self.refunded(e.account, &e.rewards)?;
+                let _ = self.accumulated.insert(e.account, refunded);
+                if e.kind == Some(RewardKind::StoreCost) {
+                    let store_cost = self.store_costs.entry(e.account).or_default();
+                    *store_cost = saturating_add(*store_cost, e.rewards.reward);
+                }
+                if let Some(history) = self.history.get_mut(&e.account) {
+                    history.claimed = history
+                        .claimed
+                        .checked_sub(e.rewards.reward)
+                        .unwrap_or_default();
+                }
+                let claimed = self
+                    .schedule
+                    .claimed()
+                    .checked_sub(e.rewards.reward)
+                    .unwrap_or_default();
+                self.schedule = self.schedule.with_claimed(claimed);
+                let _ = self.joined.entry(e.account).or_insert(self.epoch);
+                self.bump_generation(e.account);
+                let _ = self.refunded_claims.insert((e.account, e.claim));
+            }
+            EscrowReleased(e) => {
+                let released = self.released(&e)?;
+                self.enter_epoch(e.epoch);
+                for (id, accumulated) in released {
+                    let _ = self.escrowed.remove(&id);
+                    let _ = self.accumulated.insert(id, accumulated);
+                    self.bump_generation(id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the rewards of every account credited by the distribution, i.e.
+    /// all recipients not escrowed, with missing accounts starting out empty.
+    /// Validation and `apply` share this, so that they check the same accounts.
+    /// Returns `Error::ExcessiveValue` if any of the rewards overflow.
+    fn credited(
+        &self,
+        distribution: &HashMap<AccountId, Money>,
+        epoch: u64,
+    ) -> Result<HashMap<AccountId, RewardCounter>> {
+        let mut credited = HashMap::new();
+        for (id, amount) in distribution {
+            if self.is_escrowed(id, epoch) {
+                continue;
+            }
+            let existing = self.accumulated.get(id).cloned().unwrap_or_default();
+            let accumulated = self
+                .credit(&existing, *amount)
+                .ok_or(Error::ExcessiveValue)?;
+            let _ = credited.insert(*id, accumulated);
+        }
+        Ok(credited)
+    }
+
+    fn record_rewarded(&mut self, id: Id) {
+        if let Some(filter) = &mut self.idempotency_filter {
+            if !filter.contains(&id) {
+                filter.insert(&id);
+                self.filtered_ids += 1;
+            }
+            return;
+        }
+        if self.idempotency.insert(id.clone()) {
+            self.rewarded_order.push_back(id);
+        }
+        if let Some(max) = self.idempotency_cap {
+            // sweeping once every `max` pruned ids amortizes its cost
+            if self.drop_oldest_ids(max) && self.pruned_ids % max.max(1) as u64 == 0 {
+                self.sweep_history();
+            }
+        }
+    }
+
+    /// Adds the ids to those already rewarded, so that they are not rewarded again,
+    /// e.g. when seeding a new instance with ids from `export_idempotency`.
+    /// The ids count as the most recently rewarded, in the given order.
+    pub fn import_idempotency(&mut self, ids: impl IntoIterator<Item = Id>) {
+        for id in ids {
+            self.record_rewarded(id);
+        }
+    }
+
+    /// Drops the oldest rewarded ids, keeping the given number of the most recently rewarded.
+    /// Dropped ids are no longer recognised as rewarded, so they can be accumulated again.
+    /// The history is pruned along with them: the contributions and fingerprints rewarded
+    /// under the dropped ids, and the generations and join epochs of removed accounts.
+    pub fn prune_idempotency(&mut self, keep: usize) {
+        let _ = self.drop_oldest_ids(keep);
+        self.sweep_history();
+    }
+
+    /// Returns whether any ids were dropped.
+    fn drop_oldest_ids(&mut self, keep: usize) -> bool {
+        let pruned = self.pruned_ids;
+        while self.rewarded_order.len() > keep {
+            if let Some(id) = self.rewarded_order.pop_front() {
+                let _ = self.idempotency.remove(&id);
+                self.pruned_ids += 1;
+            }
+        }
+        self.pruned_ids > pruned
+    }
+
+    fn sweep_history(&mut self) {
+        let pruned = self.pruned_ids;
+        for history in self.history.values_mut() {
+            while matches!(history.contributions.front(), Some((_, _, position)) if *position < pruned)
+            {
+                let _ = history.contributions.pop_front();
+            }
+        }
+        for fingerprints in self.fingerprints.values_mut() {
+            fingerprints.retain(|_, position| *position >= pruned);
+        }
+        self.fingerprints
+            .retain(|_, fingerprints| !fingerprints.is_empty());
+        let accumulated = &self.accumulated;
+        let escrowed = &self.escrowed;
+        let present = |id: &AccountId| accumulated.contains_key(id) || escrowed.contains_key(id);
+        self.generations.retain(|id, _| present(id));
+        self.joined.retain(|id, _| present(id));
+    }
+
+    /// Adds the reward as per the overflow mode,
+    /// returning None if it overflows in checked mode.
+    fn credit(&self, counter: &RewardCounter, reward: Money) -> Option<RewardCounter> {
+        match self.overflow_mode {
+            OverflowMode::Checked => credit(counter, reward),
+            OverflowMode::Saturating => Some(saturating_credit(counter, reward)),
+        }
+    }
+
+    /// Whether rewards to the account at the epoch are held in escrow, see `with_escrow_until_age`.
+    fn is_escrowed(&self, account: &AccountId, epoch: u64) -> bool {
+        match self.escrow_until_age {
+            None => false,
+            Some(age) => {
+                let joined = self.joined.get(account).copied().unwrap_or(epoch);
+                epoch.saturating_sub(joined) < age
+            }
+        }
+    }
+
+    /// Holds the event pending, to be applied by `commit_staged`
+    /// or dropped by `discard_staged`. Staged events are not visible
+    /// to queries, nor to the validation of cmds. The event is assigned
+    /// the sequence number following the applied and already staged events.
+    pub fn stage(&mut self, mut event: AccumulationEvent) {
+        event.set_seq(self.next_seq + self.staged.len() as u64);
+        self.staged.push(event);
+    }
+
+    /// Returns the staged events, in the order they were staged.
+    pub fn staged(&self) -> &[AccumulationEvent] {
+        &self.staged
+    }
+
+    /// Applies all staged events, in the order they were staged.
+    /// The events are validated together against the current state first,
+    /// so that either all of them are applied, or none is and they stay staged.
+    /// Subscribers are notified of the events once all of them are applied.
+    pub fn commit_staged(&mut self) -> Result<()> {
+        let mut candidate = self.clone();
+        candidate.staged.clear();
+        for event in &self.staged {
+            candidate.validate(event)?;
+            candidate.apply(event.clone())?;
+        }
+        let committed = std::mem::take(&mut self.staged);
+        candidate.subscribers = std::mem::take(&mut self.subscribers);
+        *self = candidate;
+        for event in &committed {
+            self.notify(event);
+        }
+        Ok(())
+    }
+
+    /// Drops all staged events.
+    pub fn discard_staged(&mut self) {
+        self.staged.clear();
+    }
+
+    /// Merges the state of a peer, where for every account, the entry with the
+    /// higher generation wins (or ours, if equal), i.e. the most recently updated one.
+    /// This includes removals, so an account claimed after the peer's last update
+    /// of it, stays removed. The rewarded ids are the union of both.
+    /// All other state, such as history and configuration, is kept from this instance.
+    /// Idempotency filters are merged as with `merge`, returning `Error::InvalidOperation`
+    /// if the other instance has a filter that can't be merged into one of this instance.
+    pub fn merge_by_generation(mut self, other: Accumulation) -> Result<Accumulation> {
+        self.merge_filter(&other)?;
+        for id in other.rewarded_order {
+            self.record_rewarded(id);
+        }
+        for (account, generation) in other.generations {
+            if generation <= self.generation(&account) {
+                continue;
+            }
+            let _ = self.generations.insert(account, generation);
+            let _ = match other.accumulated.get(&account) {
+                None => self.accumulated.remove(&account),
+                Some(entry) => self.accumulated.insert(account, entry.clone()),
+            };
+            let _ = match other.store_costs.get(&account) {
+                None => self.store_costs.remove(&account),
+                Some(store_cost) => self.store_costs.insert(account, *store_cost),
+            };
+        }
+        Ok(self)
+    }
+
+    fn merge_filter(&mut self, other: &Accumulation) -> Result<()> {
+        if let Some(theirs) = &other.idempotency_filter {
+            match &mut self.idempotency_filter {
+                None => return Err(Error::InvalidOperation),
+                Some(ours) => ours.union(theirs)?,
+            }
+            self.filtered_ids = self.filtered_ids.saturating_add(other.filtered_ids);
+        }
+        Ok(())
+    }
+
+    /// Combines the state of another section, such as when sections merge.
+    /// The rewarded ids are the union of both, and for accounts present in both,
+    /// the rewards and the work are summed. Returns `Error::ExcessiveValue`
+    /// if the rewards of an account overflow. Likewise, the escrowed rewards, the reward pools
+    /// and the claimed totals are summed, and accounts keep the earliest of their join epochs.
+    /// An unlimited reward pool of this instance stays unlimited.
+    /// All other state, such as history and configuration, is kept from this instance.
+    /// Ids held in an idempotency filter (see `with_idempotency_filter`) can only be merged
+    /// into a filter of the same size, so merging an instance with a filter returns
+    /// `Error::InvalidOperation` unless this instance has such a filter.
+    pub fn merge(mut self, other: Accumulation) -> Result<Accumulation> {
+        self.merge_filter(&other)?;
+        for (account, theirs) in other.accumulated {
+            let merged = match self.accumulated.get(&account) {
+                None => theirs,
+                Some(ours) => RewardCounter {
+                    reward: ours
+                        .reward
+                        .checked_add(theirs.reward)
+                        .ok_or(Error::ExcessiveValue)?,
+                    work: ours.work.saturating_add(theirs.work),
+                },
+            };
+            let _ = self.accumulated.insert(account, merged);
+            self.bump_generation(account);
+        }
+        for (account, theirs) in other.store_costs {
+            let store_cost = self.store_costs.entry(account).or_default();
+            *store_cost = saturating_add(*store_cost, theirs);
+        }
+        for (account, theirs) in other.escrowed {
+            let escrowed = self.escrowed.entry(account).or_default();
+            *escrowed = escrowed.checked_add(theirs).ok_or(Error::ExcessiveValue)?;
+        }
+        self.refunded_claims.extend(other.refunded_claims);
+        for (account, theirs) in other.joined {
+            let joined = self.joined.entry(account).or_insert(theirs);
+            *joined = (*joined).min(theirs);
+        }
+        if let (Some(ours), Some(theirs)) = (self.pool, other.pool) {
+            self.pool = Some(ours.checked_add(theirs).ok_or(Error::ExcessiveValue)?);
+        }
+        let claimed = saturating_add(self.schedule.claimed(), other.schedule.claimed());
+        self.schedule = self.schedule.with_claimed(claimed);
+        for id in other.rewarded_order {
+            self.record_rewarded(id);
+        }
+        Ok(self)
+    }
+
+    /// Partitions the state by account, such as when a section splits,
+    /// returning the accounts matching the predicate, and those that don't.
+    /// The rewarded ids apply to all accounts, so both halves keep all of them, so that
+    /// neither rewards already rewarded data again. Buffered rewards are split by recipient,
+    /// configuration is kept in both, and staged events are dropped.
+    /// The remaining reward pool and the claimed total are kept by the matching half only,
+    /// so that the emission budget is not doubled; the rest starts with an empty pool (if any)
+    /// and nothing claimed. Merging the halves again, see `merge`, restores both.
+    pub fn split<F: Fn(&AccountId) -> bool>(self, pred: F) -> (Accumulation, Accumulation) {
+        let mut matching = self;
+        matching.staged.clear();
+        let mut rest = matching.clone();
+        matching.retain_accounts(|id| pred(id));
+        rest.retain_accounts(|id| !pred(id));
+        rest.pool = rest.pool.map(|_| Money::zero());
+        rest.schedule = rest.schedule.with_claimed(Money::zero());
+        (matching, rest)
+    }
+
+    fn retain_accounts(&mut self, keep: impl Fn(&AccountId) -> bool) {
+        self.accumulated.retain(|id, _| keep(id));
+        self.store_costs.retain(|id, _| keep(id));
+        self.fingerprints.retain(|id, _| keep(id));
+        self.history.retain(|id, _| keep(id));
+        self.epoch_rewards.retain(|id, _| keep(id));
+        for epoch in self.past_epochs.iter_mut() {
+            epoch.retain(|id, _| keep(id));
+        }
+        self.generations.retain(|id, _| keep(id));
+        self.joined.retain(|id, _| keep(id));
+        self.escrowed.retain(|id, _| keep(id));
+        self.refunded_claims.retain(|(id, _)| keep(id));
+        self.resets.retain(|reset| keep(&reset.account));
+        for distribution in self.buffered.values_mut() {
+            distribution.retain(|id, _| keep(id));
+        }
+        self.buffered
+            .retain(|_, distribution| !distribution.is_empty());
+    }
+
+    fn bump_generation(&mut self, account: AccountId) {
+        let generation = self.generations.entry(account).or_insert(0);
+        *generation = generation.saturating_add(1);
+    }
+
+    /// Buffers a reward, to be accumulated together with other
+    /// buffered rewards by `flush_accumulated`, reducing the number of events.
+    /// The reward is validated as with `accumulate`, and the id must not already be buffered.
+    pub fn buffer(&mut self, id: Id, distribution: HashMap<AccountId, Money>) -> Result<()> {
+        if self.buffered.contains_key(&id) {
+            return Err(Error::DataExists);
+        }
+        let e = self.accumulate(id, distribution)?;
+        let _ = self.buffered.insert(e.id, e.distribution);
+        Ok(())
+    }
+
+    /// Releases the escrowed rewards of every account that has reached
+    /// the escrow age at the given epoch, to be credited when the event is applied.
+    /// Rewards which would overflow the balance, or raise it above
+    /// the max balance (see `with_max_balance`), stay in escrow.
+    /// Applying the event enters the epoch, if later than the current one.
+    pub fn release_escrow(&self, current_epoch: u64) -> EscrowReleased {
+        let released = self
+            .escrowed
+            .iter()
+            .filter(|(id, _)| !self.is_escrowed(id, current_epoch))
+            .filter(|(id, amount)| {
+                let existing = self.accumulated.get(id).cloned().unwrap_or_default();
+                match self.credit(&existing, **amount) {
+                    None => false,
+                    Some(accumulated) => self.check_max_balance(accumulated.reward).is_ok(),
+                }
+            })
+            .map(|(id, amount)| (*id, *amount))
+            .collect();
+        EscrowReleased {
+            epoch: current_epoch,
+            released,
+            seq: self.next_seq,
+        }
+    }
+
+    /// Returns the rewards of the accounts with the released escrow credited.
+    /// Returns `Error::InvalidOperation` if a release does not match a matured escrow
+    /// or raises the balance above the max, and `Error::ExcessiveValue` if it overflows the balance.
+    fn released(&self, e: &EscrowReleased) -> Result<HashMap<AccountId, RewardCounter>> {
+        let mut released = HashMap::new();
+        for (id, amount) in &e.released {
+            if self.escrowed.get(id) != Some(amount) || self.is_escrowed(id, e.epoch) {
+                return Err(Error::InvalidOperation);
+            }
+            let existing = self.accumulated.get(id).cloned().unwrap_or_default();
+            let accumulated = self
+                .credit(&existing, *amount)
+                .ok_or(Error::ExcessiveValue)?;
+            self.check_max_balance(accumulated.reward)?;
+            let _ = released.insert(*id, accumulated);
+        }
+        Ok(released)
+    }
+
+    /// Starts a new epoch, resetting the per epoch
+    /// rewards that are limited by the account epoch cap.
+    /// The rewards of the ended epoch are retained for `reward_velocity`.
+    /// Events produced from then on carry the new epoch,
+    /// which is entered when they are applied to another instance.
+    pub fn advance_epoch(&mut self) {
+        let ended = std::mem::take(&mut self.epoch_rewards);
+        self.past_epochs.push_front(ended);
+        self.past_epochs.truncate(RETAINED_EPOCHS);
+        self.epoch += 1;
+    }
+
+    /// Advances to the epoch, if later than the current one.
+    fn enter_epoch(&mut self, epoch: u64) {
+        // beyond the retained epochs, all are empty
+        let retained = RETAINED_EPOCHS as u64 + 1;
+        for _ in 0..epoch.saturating_sub(self.epoch).min(retained) {
+            self.advance_epoch();
+        }
+        self.epoch = self.epoch.max(epoch);
+    }
+
+    /// Operator override for an account whose entry is corrupt.
+    /// Overwrites the entry with the given reward and work, bypassing
+    /// all validation, and records the reason for audit.
+    /// This is not an event, and will not be reproduced by replaying events,
+    /// so it is only to be used for recovering from corrupted state.
+    pub fn force_reset_account(
+        &mut self,
+        account: AccountId,
+        amount: Money,
+        worked: Work,
+        reason: String,
+    ) {
+        let current = RewardCounter {
+            reward: amount,
+            work: worked,
+        };
+        self.bump_generation(account);
+        let previous = self.accumulated.insert(account, current.clone());
+        warn!(
+            "Account {:?} was reset from {:?} to {:?}. Reason: {}",
+            account, previous, current, reason
+        );
+        self.resets.push(AccountReset {
+            account,
+            previous,
+            current,
+            reason,
+        });
+    }
+
+    /// Recomputes the work of the accounts by replaying
+    /// the work bearing events of the log, leaving rewards untouched.
+    /// Used to restore work when recovering from a snapshot that lacks it.
+    /// Accounts that are not in the log keep their current work.
+    /// As when applied, a claim only removes the work if it empties the balance.
+    pub fn rebuild_work(&mut self, events: &[AccumulationEvent]) {
+        use AccumulationEvent::*;
+        let mut work: HashMap<AccountId, Work> = HashMap::new();
+        let mut balances: HashMap<AccountId, Money> = HashMap::new();
+        for event in events {
+            match event {
+                AccountAdded(e) => {
+                    let _ = work.insert(e.id, e.work);
+                }
+                RewardsAccumulated(e) => {
+                    for (id, amount) in &e.distribution {
+                        let counter = work.entry(*id).or_insert(0);
+                        *counter = counter.saturating_add(1);
+                        let balance = balances.entry(*id).or_default();
+                        *balance = saturating_add(*balance, *amount);
+                    }
+                }
+                RewardsClaimed(e) => {
+                    let remaining = balances
+                        .get(&e.account)
+                        .and_then(|balance| balance.checked_sub(e.rewards.reward))
+                        .filter(|remaining| *remaining > Money::zero());
+                    match remaining {
+                        Some(remaining) => {
+                            let _ = balances.insert(e.account, remaining);
+                        }
+                        None => {
+                            let _ = balances.remove(&e.account);
+                            let _ = work.remove(&e.account);
+                        }
+                    }
+                }
+                RewardsTransferred(e) => {
+                    if let Some(moved) = work.remove(&e.from) {
+                        let _ = work.insert(e.to, moved);
+                    }
+                    if let Some(moved) = balances.remove(&e.from) {
+                        let _ = balances.insert(e.to, moved);
+                    }
+                }
+                AccountClosed(e) => {
+                    let _ = work.remove(&e.account);
+                    let _ = balances.remove(&e.account);
+                    if let Some(to) = e.forfeit_to {
+                        let balance = balances.entry(to).or_default();
+                        *balance = saturating_add(*balance, e.forfeited);
+                    }
+                }
+                WorkDecayed(e) => {
+                    if e.denominator > 0 && e.numerator <= e.denominator {
+                        for counter in work.values_mut() {
+                            *counter = decay(*counter, e.numerator, e.denominator);
+                        }
+                    }
+                }
+                ClaimRefunded(e) => {
+                    let _ = work.entry(e.account).or_insert(e.rewards.work);
+                    let balance = balances.entry(e.account).or_default();
+                    *balance = saturating_add(*balance, e.rewards.reward);
+                }
+                EscrowReleased(_) => (),
+            }
+        }
+        for (id, acc) in self.accumulated.iter_mut() {
+            if let Some(work) = work.get(id) {
+                acc.work = *work;
+            }
+        }
+    }
+}
+
+/// Adds the reward and increments the work, clamping both at their max values.
+fn saturating_credit(counter: &RewardCounter, reward: Money) -> RewardCounter {
+    RewardCounter {
+        reward: saturating_add(counter.reward, reward),
+        work: counter.work.saturating_add(1),
+    }
+}
+
+/// Hashes the values ordered by account, see `Accumulation::state_hash`.
+fn hash_by_account(hasher: &mut Sha3, mut entries: Vec<(AccountId, u64)>) -> Result<()> {
+    entries.sort_by_key(|(id, _)| *id);
+    hasher.update(&(entries.len() as u64).to_le_bytes());
+    for (id, value) in entries {
+        let id = bincode::serialize(&id).map_err(|e| Error::FailedToParse(e.to_string()))?;
+        hasher.update(&(id.len() as u64).to_le_bytes());
+        hasher.update(&id);
+        hasher.update(&value.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Scales the work by the fraction, rounding down.
+/// The fraction is at most one, so the work never grows.
+fn decay(work: Work, numerator: Work, denominator: Work) -> Work {
+    let scaled = u128::from(work) * u128::from(numerator) / u128::from(denominator);
+    Work::try_from(scaled).unwrap_or(work).min(work)
+}
+
+/// Sets or removes the entry, as per the value.
+fn restore<V>(map: &mut HashMap<AccountId, V>, key: AccountId, value: Option<V>) {
+    let _ = match value {
+        None => map.remove(&key),
+        Some(value) => map.insert(key, value),
+    };
+}
+
+/// Sums the amounts, returning None on overflow.
+fn sum<'a>(mut amounts: impl Iterator<Item = &'a Money>) -> Option<Money> {
+    amounts.try_fold(Money::zero(), |sum, amount| sum.checked_add(*amount))
+}
+
+/// Adds the amounts, clamping at the max value.
+fn saturating_add(a: Money, b: Money) -> Money {
+    a.checked_add(b)
+        .unwrap_or_else(|| Money::from_nano(u64::MAX))
+}
+
+/// Adds the reward to the counter, and increments the work.
+/// Returns None if the reward would overflow.
+/// The work saturates at its max value, since wrapping around
+/// would reset the account's share of work.
+fn credit(counter: &RewardCounter, reward: Money) -> Option<RewardCounter> {
+    Some(RewardCounter {
+        reward: counter.reward.checked_add(reward)?,
+        work: counter.work.saturating_add(1),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Accumulation, AccumulationEvent, AccumulationSnapshot, Mint, MintReceipt, OverflowMode,
+        RewardKind, RewardSchedule, RewardsAccumulated, RewardsClaimed, WorkDecayed,
+        API_SNAPSHOT_VERSION, RETAINED_CONTRIBUTIONS, RETAINED_UNDO,
+    };
+    use crate::{
+        bloom::BloomFilter,
+        merkle::{leaf, MerkleTree},
+    };
+    use safe_nd::{Error, Money, PublicKey, RewardCounter};
+    use std::{
+        cell::Cell,
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+    use threshold_crypto::SecretKey;
+
+    macro_rules! hashmap {
+        ($( $key: expr => $val: expr ),*) => {{
+             let mut map = ::std::collections::HashMap::new();
+             $( let _ = map.insert($key, $val); )*
+             map
+        }}
+    }
+
+    #[test]
+    fn when_data_was_not_previously_rewarded_reward_accumulates() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let data_hash = vec![1, 2, 3];
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![account => reward];
+
+        // --- Act ---
+        // Try accumulate.
+        let e = acc.accumulate(data_hash, distribution)?;
+
+        // --- Assert ---
+        // Confirm valid ..
+        assert!(e.distribution.len() == 1);
+        assert!(e.distribution.contains_key(&account));
+        assert_eq!(&reward, e.distribution.get(&account).unwrap());
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        // .. and successful.
+        if let Some(accumulated) = acc.get(&account) {
+            assert_eq!(accumulated.reward, reward);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn when_data_is_already_rewarded_accumulation_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let data_hash = vec![1, 2, 3];
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![account => reward];
+
+        // Accumulate reward.
+        let reward = acc.accumulate(data_hash.clone(), distribution.clone())?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(reward))?;
+
+        // --- Act ---
+        // Try same data hash again ..
+
+        // --- Assert ---
+        // .. confirm not successful.
+        assert_eq!(
+            acc.accumulate(data_hash, distribution),
+            Err(Error::DataExists)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn when_account_has_reward_it_can_claim() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let data_hash = vec![1, 2, 3];
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![account => reward];
+        let accumulation = acc.accumulate(data_hash, distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(accumulation))?;
+
+        // --- Act + Assert ---
+        // Try claim, confirm account and amount is correct.
+        let e = acc.claim(account)?;
+        assert!(e.account == account);
+        assert!(e.rewards.reward == reward);
+        acc.apply(AccumulationEvent::RewardsClaimed(e))?;
+        Ok(())
+    }
+
+    #[test]
+    fn when_reward_was_claimed_it_can_not_be_claimed_again() {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let data_hash = vec![1, 2, 3];
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![account => reward];
+
+        let accumulation = acc.accumulate(data_hash, distribution).unwrap();
+        acc.apply(AccumulationEvent::RewardsAccumulated(accumulation))
+            .unwrap();
+
+        // Claim the account reward.
+        let claim = acc.claim(account).unwrap();
+        acc.apply(AccumulationEvent::RewardsClaimed(claim)).unwrap();
+
+        // --- Act ---
+        // Try claim the account reward again ..
+        let result = acc.claim(account);
+
+        // --- Assert ---
+        // .. confirm not successful.
+        assert_eq!(result, Err(Error::NoSuchKey))
+    }
+
+    #[test]
+    fn when_account_has_no_reward_it_can_not_claim() {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+
+        // --- Act + Assert ---
+        // Try claim the account reward again, confirm not successful.
+        let result = acc.claim(account);
+        match result {
+            Ok(_) => panic!(),
+            Err(err) => assert_eq!(err, Error::NoSuchKey),
+        }
+    }
+
+    #[test]
+    fn when_reward_was_claimed_get_returns_none() {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let data_hash = vec![1, 2, 3];
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![account => reward];
+        let accumulation = acc.accumulate(data_hash, distribution).unwrap();
+        acc.apply(AccumulationEvent::RewardsAccumulated(accumulation))
+            .unwrap();
+        let claim = acc.claim(account).unwrap();
+        acc.apply(AccumulationEvent::RewardsClaimed(claim)).unwrap();
+
+        // --- Act ---
+        // Try get the account reward.
+        let result = acc.get(&account);
+
+        // --- Assert ---
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn when_batch_has_duplicate_ids_strict_batch_is_rejected() {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let entries = vec![
+            (vec![1, 2, 3], hashmap![account => Money::from_nano(10)]),
+            (vec![4, 5, 6], hashmap![account => Money::from_nano(20)]),
+            (vec![1, 2, 3], hashmap![account => Money::from_nano(30)]),
+        ];
+
+        // --- Act ---
+        let result = acc.accumulate_batch(entries, false);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::DataExists));
+    }
+
+    #[test]
+    fn when_batch_has_duplicate_ids_dedupe_batch_skips_later_occurrences() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let entries = vec![
+            (vec![1, 2, 3], hashmap![account => Money::from_nano(10)]),
+            (vec![4, 5, 6], hashmap![account => Money::from_nano(20)]),
+            (vec![1, 2, 3], hashmap![account => Money::from_nano(30)]),
+        ];
+
+        // --- Act ---
+        let (events, skipped) = acc.accumulate_batch(entries, true)?;
+
+        // --- Assert ---
+        // The first occurrence wins ..
+        assert_eq!(events.len(), 2);
+        assert_eq!(skipped, vec![vec![1, 2, 3]]);
+        for e in events {
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        // .. and the later one is not accumulated.
+        match acc.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => assert_eq!(accumulated.reward, Money::from_nano(30)),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_work_reaches_max_it_saturates() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let added = acc.add_account(account, u64::MAX - 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+        let reward = Money::from_nano(10);
+
+        // --- Act ---
+        // Accumulate until work reaches max ..
+        let e = acc.accumulate(vec![1], hashmap![account => reward])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert!(acc.work_saturated(&account));
+        // .. and beyond.
+        let e = acc.accumulate(vec![2], hashmap![account => reward])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        // Confirm work saturated, while reward still accumulates.
+        assert!(acc.work_saturated(&account));
+        match acc.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => {
+                assert_eq!(accumulated.work, u64::MAX);
+                assert_eq!(accumulated.reward, Money::from_nano(20));
+            }
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_pool_covers_reward_it_accumulates_and_pool_is_drawn_down() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_reward_pool(Money::from_nano(30));
+        let account = get_random_pk();
+        let distribution =
+            hashmap![account => Money::from_nano(10), get_random_pk() => Money::from_nano(20)];
+
+        // --- Act ---
+        let e = acc.accumulate(vec![1, 2, 3], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        assert_eq!(acc.pool_balance(), Some(Money::zero()));
+        match acc.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => assert_eq!(accumulated.reward, Money::from_nano(10)),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_pool_is_exhausted_accumulation_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_reward_pool(Money::from_nano(15));
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let result = acc.accumulate(vec![2], hashmap![account => Money::from_nano(10)]);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::InsufficientBalance));
+        assert_eq!(acc.pool_balance(), Some(Money::from_nano(5)));
+        Ok(())
+    }
+
+    #[test]
+    fn when_work_is_missing_it_is_rebuilt_from_events() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let alice = get_random_pk();
+        let bob = get_random_pk();
+        let mut events = vec![AccumulationEvent::AccountAdded(acc.add_account(alice, 5)?)];
+        acc.apply(events[0].clone())?;
+        let distributions = vec![
+            hashmap![alice => Money::from_nano(10), bob => Money::from_nano(10)],
+            hashmap![alice => Money::from_nano(10)],
+        ];
+        for (i, distribution) in distributions.into_iter().enumerate() {
+            let e = acc.accumulate(vec![i as u8], distribution)?;
+            let e = AccumulationEvent::RewardsAccumulated(e);
+            acc.apply(e.clone())?;
+            events.push(e);
+        }
+
+        // Clear work, as in a balances-only snapshot.
+        let balances = acc
+            .get_all()
+            .iter()
+            .map(|(id, counter)| {
+                let mut counter = counter.clone();
+                counter.work = 0;
+                (*id, counter)
+            })
+            .collect();
+        let mut recovered = Accumulation::new(Default::default(), balances);
+
+        // --- Act ---
+        recovered.rebuild_work(&events);
+
+        // --- Assert ---
+        assert_eq!(recovered.get_all(), acc.get_all());
+        match recovered.get(&alice) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => {
+                assert_eq!(accumulated.work, 7);
+                assert_eq!(accumulated.reward, Money::from_nano(20));
+            }
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_fee_is_below_balance_claim_is_economical() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let economics = acc
+            .claim_economics(&account, Money::from_nano(3))
+            .ok_or(Error::NoSuchKey)?;
+
+        // --- Assert ---
+        assert_eq!(economics.balance, Money::from_nano(10));
+        assert_eq!(economics.fee, Money::from_nano(3));
+        assert_eq!(economics.net, Money::from_nano(7));
+        assert!(!economics.uneconomical);
+        Ok(())
+    }
+
+    #[test]
+    fn when_fee_exceeds_balance_claim_is_uneconomical() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let economics = acc
+            .claim_economics(&account, Money::from_nano(11))
+            .ok_or(Error::NoSuchKey)?;
+
+        // --- Assert ---
+        assert_eq!(economics.net, Money::zero());
+        assert!(economics.uneconomical);
+        assert!(acc
+            .claim_economics(&get_random_pk(), Money::from_nano(1))
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn when_fingerprint_was_rewarded_to_account_accumulation_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let fingerprint = vec![9, 9, 9];
+        let e = acc.accumulate_unique(
+            vec![1],
+            hashmap![account => Money::from_nano(10)],
+            fingerprint.clone(),
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        // Same logical work, under another id.
+        let result = acc.accumulate_unique(
+            vec![2],
+            hashmap![account => Money::from_nano(10)],
+            fingerprint,
+        );
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::DataExists));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_and_apply_reject_rewarded_fingerprint() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e =
+            acc.accumulate_unique(vec![1], hashmap![account => Money::from_nano(10)], vec![9])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        // Same logical work, under another id, bypassing the cmd.
+        let mut e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(10)])?;
+        e.fingerprint = Some(vec![9]);
+        let e = AccumulationEvent::RewardsAccumulated(e);
+
+        // --- Act ---
+        let validated = acc.validate(&e);
+        let applied = acc.apply(e);
+
+        // --- Assert ---
+        assert_eq!(validated, Err(Error::DataExists));
+        assert_eq!(applied, Err(Error::DataExists));
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(10))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn when_fingerprint_is_new_to_account_reward_accumulates() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e =
+            acc.accumulate_unique(vec![1], hashmap![account => Money::from_nano(10)], vec![9])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let e =
+            acc.accumulate_unique(vec![2], hashmap![account => Money::from_nano(10)], vec![8])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        match acc.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => assert_eq!(accumulated.reward, Money::from_nano(20)),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_account_is_reset_it_has_new_values_and_reset_is_recorded() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let corrupt = RewardCounter {
+            reward: Money::from_nano(u64::MAX),
+            work: 0,
+        };
+        let mut acc = Accumulation::new(Default::default(), hashmap![account => corrupt.clone()]);
+
+        // --- Act ---
+        acc.force_reset_account(account, Money::from_nano(10), 3, "corrupt".to_string());
+
+        // --- Assert ---
+        let expected = RewardCounter {
+            reward: Money::from_nano(10),
+            work: 3,
+        };
+        assert_eq!(acc.get(&account), Some(&expected));
+        assert_eq!(acc.resets().len(), 1);
+        assert_eq!(acc.resets()[0].account, account);
+        assert_eq!(acc.resets()[0].previous, Some(corrupt));
+        assert_eq!(acc.resets()[0].current, expected);
+        assert_eq!(acc.resets()[0].reason, "corrupt");
+        Ok(())
+    }
+
+    #[test]
+    fn when_rewards_are_equal_fairness_index_is_zero() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![get_random_pk() => reward, get_random_pk() => reward, get_random_pk() => reward];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act + Assert ---
+        assert!(acc.fairness_index().abs() < 0.000_001);
+        Ok(())
+    }
+
+    #[test]
+    fn when_rewards_are_skewed_fairness_index_is_high() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        for _ in 0..3 {
+            let added = acc.add_account(get_random_pk(), 1)?;
+            acc.apply(AccumulationEvent::AccountAdded(added))?;
+        }
+        let e = acc.accumulate(vec![1], hashmap![get_random_pk() => Money::from_nano(100)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act + Assert ---
+        // All rewards held by one of four accounts: (n - 1) / n.
+        assert!((acc.fairness_index() - 0.75).abs() < 0.000_001);
+        Ok(())
+    }
+
+    #[test]
+    fn when_resolver_approves_recipients_they_are_added_and_rewarded() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let registered = get_random_pk();
+        let added = acc.add_account(registered, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+        let unregistered = get_random_pk();
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![registered => reward, unregistered => reward];
+
+        // --- Act ---
+        let (added, e) = acc.accumulate_with_resolver(vec![1], distribution, |_| Some(5))?;
+
+        // --- Assert ---
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].id, unregistered);
+        for e in added {
+            acc.apply(AccumulationEvent::AccountAdded(e))?;
+        }
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        match acc.get(&unregistered) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => {
+                assert_eq!(accumulated.reward, reward);
+                assert_eq!(accumulated.work, 6);
+            }
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_resolver_rejects_a_recipient_accumulation_is_rejected() {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let approved = get_random_pk();
+        let rejected = get_random_pk();
+        let reward = Money::from_nano(10);
+        let distribution = hashmap![approved => reward, rejected => reward];
+
+        // --- Act ---
+        let result = acc.accumulate_with_resolver(vec![1], distribution, |account| {
+            if *account == approved {
+                Some(1)
+            } else {
+                None
+            }
+        });
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::NoSuchKey));
+    }
+
+    #[test]
+    fn compact_idempotency_decodes_to_rewarded_ids() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        for id in 0..5 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(1)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+
+        // --- Act ---
+        let ids = crate::compact::decode_ids(&acc.compact_idempotency())?;
+
+        // --- Assert ---
+        // Restored set rejects the same ids.
+        let restored = Accumulation::new(ids.into_iter().collect(), Default::default());
+        for id in 0..5 {
+            assert_eq!(
+                restored.accumulate(vec![id], hashmap![account => Money::from_nano(1)]),
+                Err(Error::DataExists)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn when_percentages_do_not_split_evenly_amounts_still_sum_to_total() -> Result<(), Error> {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let accounts: Vec<_> = (0..3).map(|_| get_random_pk()).collect();
+        let shares = vec![
+            (accounts[0], 3334),
+            (accounts[1], 3333),
+            (accounts[2], 3333),
+        ];
+
+        // --- Act ---
+        let e = acc.accumulate_percentages(vec![1], Money::from_nano(100), shares)?;
+
+        // --- Assert ---
+        let amounts: Vec<u64> = accounts
+            .iter()
+            .map(|account| e.distribution[account].as_nano())
+            .collect();
+        assert_eq!(amounts.iter().sum::<u64>(), 100);
+        assert_eq!(amounts[0], 34);
+        assert_eq!(amounts[1], 33);
+        assert_eq!(amounts[2], 33);
+        Ok(())
+    }
+
+    #[test]
+    fn when_percentages_do_not_sum_to_whole_accumulation_is_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let shares = vec![(get_random_pk(), 5000), (get_random_pk(), 4999)];
+        let result = acc.accumulate_percentages(vec![1], Money::from_nano(100), shares);
+        assert_eq!(result, Err(Error::InvalidOperation));
+    }
+
+    #[test]
+    fn when_inflation_changes_the_total_percentages_are_rejected() {
+        let acc =
+            Accumulation::new(Default::default(), Default::default()).with_inflation_factor(2.0);
+        let shares = vec![(get_random_pk(), 5000), (get_random_pk(), 5000)];
+        let result = acc.accumulate_percentages(vec![1], Money::from_nano(100), shares);
+        assert_eq!(result, Err(Error::InvalidOperation));
+    }
+
+    struct MockMint {
+        succeed: bool,
+        minted: Cell<Money>,
+    }
+
+    impl Mint for MockMint {
+        fn mint(&self, total: Money) -> Result<(), Error> {
+            if !self.succeed {
+                return Err(Error::InsufficientBalance);
+            }
+            self.minted.set(total);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn when_mint_succeeds_reward_accumulates() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let distribution =
+            hashmap![account => Money::from_nano(10), get_random_pk() => Money::from_nano(5)];
+        let mint = MockMint {
+            succeed: true,
+            minted: Cell::new(Money::zero()),
+        };
+
+        // --- Act ---
+        let e = acc.accumulate_minted(vec![1], distribution, &mint)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        assert_eq!(mint.minted.get(), Money::from_nano(15));
+        match acc.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => assert_eq!(accumulated.reward, Money::from_nano(10)),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_mint_fails_accumulation_is_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let mint = MockMint {
+            succeed: false,
+            minted: Cell::new(Money::zero()),
+        };
+        let result = acc.accumulate_minted(
+            vec![1],
+            hashmap![get_random_pk() => Money::from_nano(10)],
+            &mint,
+        );
+        assert_eq!(result, Err(Error::InsufficientBalance));
+    }
+
+    #[test]
+    fn statement_summarises_account_activity() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let added = acc.add_account(account, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(20)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim(account)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        let e = acc.accumulate(vec![3], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let statement = acc.statement(&account).ok_or(Error::NoSuchKey)?;
+
+        // --- Assert ---
+        assert_eq!(statement.balance, Money::from_nano(5));
+        assert_eq!(statement.work, 1);
+        assert_eq!(statement.lifetime_earned, Money::from_nano(35));
+        assert_eq!(statement.lifetime_claimed, Money::from_nano(30));
+        assert_eq!(
+            statement.contributions,
+            vec![
+                (vec![1], Money::from_nano(10)),
+                (vec![2], Money::from_nano(20)),
+                (vec![3], Money::from_nano(5))
+            ]
+        );
+        assert!(acc.statement(&get_random_pk()).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn when_account_epoch_cap_is_reached_accumulation_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_account_epoch_cap(Money::from_nano(20));
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(15)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        // Up to the cap is fine ..
+        let e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(acc.epoch_rewards(&account), Money::from_nano(20));
+
+        // --- Act ---
+        // .. beyond it is not.
+        let result = acc.accumulate(vec![3], hashmap![account => Money::from_nano(1)]);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::InvalidOperation));
+        // Other accounts are not affected.
+        let _ = acc.accumulate(vec![3], hashmap![get_random_pk() => Money::from_nano(20)])?;
+        Ok(())
+    }
+
+    #[test]
+    fn when_epoch_advances_account_epoch_cap_resets() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_account_epoch_cap(Money::from_nano(20));
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(20)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        acc.advance_epoch();
+
+        // --- Assert ---
+        assert_eq!(acc.epoch_rewards(&account), Money::zero());
+        let e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(20)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        match acc.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => assert_eq!(accumulated.reward, Money::from_nano(40)),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn ids_without_recorded_distribution_are_reported_as_orphans() -> Result<(), Error> {
+        // --- Arrange ---
+        let orphan = vec![7, 7, 7];
+        let idempotency = vec![orphan.clone()].into_iter().collect();
+        let mut acc = Accumulation::new(idempotency, Default::default());
+        let e = acc.accumulate(vec![1], hashmap![get_random_pk() => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let orphans = acc.orphaned_ids();
+
+        // --- Assert ---
+        assert_eq!(orphans, vec![orphan]);
+        Ok(())
+    }
+
+    #[test]
+    fn when_account_reaches_auto_claim_threshold_it_is_claimed() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_auto_claim_threshold(Money::from_nano(25));
+        let account = get_random_pk();
+        let other = get_random_pk();
+        let (e, claims) = acc.accumulate_with_auto_claim(
+            vec![1],
+            hashmap![account => Money::from_nano(20), other => Money::from_nano(5)],
+        )?;
+        assert!(claims.is_empty());
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let (e, claims) = acc.accumulate_with_auto_claim(
+            vec![2],
+            hashmap![account => Money::from_nano(10), other => Money::from_nano(5)],
+        )?;
+
+        // --- Assert ---
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].account, account);
+        assert_eq!(claims[0].rewards.reward, Money::from_nano(30));
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        for claim in claims {
+            acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        }
+        assert!(acc.get(&account).is_none());
+        assert!(acc.get(&other).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn escrowed_rewards_are_not_auto_claimed() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_auto_claim_threshold(Money::from_nano(10))
+            .with_escrow_until_age(1);
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        acc.advance_epoch();
+        let released = acc.release_escrow(acc.epoch());
+        acc.apply(AccumulationEvent::EscrowReleased(released))?;
+        let young = get_random_pk();
+
+        // --- Act ---
+        let (_, claims) = acc.accumulate_with_auto_claim(
+            vec![2],
+            hashmap![account => Money::from_nano(5), young => Money::from_nano(20)],
+        )?;
+
+        // --- Assert ---
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].account, account);
+        assert_eq!(claims[0].rewards.reward, Money::from_nano(15));
+        Ok(())
+    }
+
+    #[test]
+    fn balance_below_min_claim_is_not_auto_claimed() -> Result<(), Error> {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default())
+            .with_auto_claim_threshold(Money::from_nano(10))
+            .with_min_claim(Money::from_nano(20));
+        let account = get_random_pk();
+
+        // --- Act ---
+        let (_, claims) =
+            acc.accumulate_with_auto_claim(vec![1], hashmap![account => Money::from_nano(15)])?;
+
+        // --- Assert ---
+        assert!(claims.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn valid_log_passes_validation() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let mut events = vec![];
+        let e = AccumulationEvent::AccountAdded(acc.add_account(account, 1)?);
+        acc.apply(e.clone())?;
+        events.push(e);
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        let e = AccumulationEvent::RewardsAccumulated(e);
+        acc.apply(e.clone())?;
+        events.push(e);
+        let e = AccumulationEvent::RewardsClaimed(acc.claim(account)?);
+        acc.apply(e.clone())?;
+        events.push(e);
+
+        Accumulation::validate_log(&events)
+    }
+
+    #[test]
+    fn corrupt_logs_fail_validation() -> Result<(), Error> {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let added = AccumulationEvent::AccountAdded(acc.add_account(account, 1)?);
+        let accumulated = AccumulationEvent::RewardsAccumulated(
+            acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?,
+        );
+        let overflowing = AccumulationEvent::RewardsAccumulated(
+            acc.accumulate(vec![2], hashmap![account => Money::from_nano(u64::MAX)])?,
+        );
+        let claimed = AccumulationEvent::RewardsClaimed(RewardsClaimed {
+            account,
+            rewards: Default::default(),
+            destination: account,
+            kind: None,
+            seq: 0,
+        });
+        let next = |event: &AccumulationEvent| {
+            let mut event = event.clone();
+            event.set_seq(1);
+            event
+        };
+
+        // --- Act + Assert ---
+        assert_eq!(
+            Accumulation::validate_log(&[added.clone(), next(&added)]),
+            Err(Error::BalanceExists)
+        );
+        assert_eq!(
+            Accumulation::validate_log(&[accumulated.clone(), next(&accumulated)]),
+            Err(Error::DataExists)
+        );
+        assert_eq!(
+            Accumulation::validate_log(&[accumulated.clone(), accumulated.clone()]),
+            Err(Error::InvalidOperation)
+        );
+        assert_eq!(
+            Accumulation::validate_log(&[accumulated, next(&overflowing)]),
+            Err(Error::ExcessiveValue)
+        );
+        assert_eq!(
+            Accumulation::validate_log(&[claimed]),
+            Err(Error::NoSuchKey)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reward_ids_increase_and_survive_replay() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let mut events = vec![];
+
+        // --- Act ---
+        for id in 0..3 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(1)])?;
+            let e = AccumulationEvent::RewardsAccumulated(e);
+            acc.apply(e.clone())?;
+            events.push(e);
+        }
+        let entries = vec![
+            (vec![3], hashmap![account => Money::from_nano(1)]),
+            (vec![4], hashmap![account => Money::from_nano(1)]),
+        ];
+        let (batch, _) = acc.accumulate_batch(entries, false)?;
+
+        // --- Assert ---
+        let reward_ids: Vec<u64> = events
+            .iter()
+            .filter_map(|e| match e {
+                AccumulationEvent::RewardsAccumulated(e) => Some(e.reward_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reward_ids, vec![1, 2, 3]);
+        assert_eq!(acc.last_reward_id(), 3);
+        assert_eq!(batch[0].reward_id, 4);
+        assert_eq!(batch[1].reward_id, 5);
+
+        let mut replayed = Accumulation::new(Default::default(), Default::default());
+        for e in events {
+            replayed.apply(e)?;
+        }
+        assert_eq!(replayed.last_reward_id(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn when_merging_by_generation_most_recently_updated_entry_wins() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let mut ours = Accumulation::new(Default::default(), Default::default());
+        let e = ours.accumulate(vec![1], hashmap![account => Money::from_nano(100)])?;
+        ours.apply(AccumulationEvent::RewardsAccumulated(e.clone()))?;
+        let mut theirs = Accumulation::new(Default::default(), Default::default());
+        theirs.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        // The peer has seen more updates, though with a lower balance.
+        let e = theirs.accumulate(vec![2], hashmap![account => Money::from_nano(1)])?;
+        theirs.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(ours.generation(&account), 1);
+        assert_eq!(theirs.generation(&account), 2);
+
+        // --- Act ---
+        let merged = ours.merge_by_generation(theirs)?;
+
+        // --- Assert ---
+        match merged.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => assert_eq!(accumulated.reward, Money::from_nano(101)),
+        };
+        assert_eq!(merged.generation(&account), 2);
+        assert_eq!(
+            merged.accumulate(vec![2], hashmap![account => Money::from_nano(1)]),
+            Err(Error::DataExists)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn when_merging_by_generation_newer_removal_wins() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let mut ours = Accumulation::new(Default::default(), Default::default());
+        let e = ours.accumulate(vec![1], hashmap![account => Money::from_nano(100)])?;
+        ours.apply(AccumulationEvent::RewardsAccumulated(e.clone()))?;
+        let mut theirs = Accumulation::new(Default::default(), Default::default());
+        theirs.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = theirs.claim(account)?;
+        theirs.apply(AccumulationEvent::RewardsClaimed(claim))?;
+
+        // --- Act ---
+        let merged = ours.merge_by_generation(theirs)?;
+
+        // --- Assert ---
+        assert!(merged.get(&account).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn when_proofs_are_valid_proven_rewards_accumulate() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let eligible: Vec<_> = (1..6)
+            .map(|i| (get_random_pk(), Money::from_nano(i)))
+            .collect();
+        let leaves = eligible
+            .iter()
+            .map(|(account, amount)| leaf(account, *amount))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let tree = MerkleTree::new(leaves);
+        let root = tree.root().ok_or(Error::NoSuchData)?;
+        // Only a subset of the eligible is passed in.
+        let entries = vec![
+            (
+                eligible[1].0,
+                eligible[1].1,
+                tree.proof(1).ok_or(Error::NoSuchData)?,
+            ),
+            (
+                eligible[4].0,
+                eligible[4].1,
+                tree.proof(4).ok_or(Error::NoSuchData)?,
+            ),
+        ];
+
+        // --- Act ---
+        let e = acc.accumulate_proven(vec![1], root, entries)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        assert_eq!(acc.get_all().len(), 2);
+        match acc.get(&eligible[4].0) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => assert_eq!(accumulated.reward, Money::from_nano(5)),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn when_a_proof_is_invalid_proven_accumulation_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let eligible: Vec<_> = (1..4)
+            .map(|i| (get_random_pk(), Money::from_nano(i)))
+            .collect();
+        let leaves = eligible
+            .iter()
+            .map(|(account, amount)| leaf(account, *amount))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let tree = MerkleTree::new(leaves);
+        let root = tree.root().ok_or(Error::NoSuchData)?;
+        // Claiming a higher amount than proven.
+        let entries = vec![(
+            eligible[0].0,
+            Money::from_nano(100),
+            tree.proof(0).ok_or(Error::NoSuchData)?,
+        )];
+
+        // --- Act ---
+        let result = acc.accumulate_proven(vec![1], root, entries);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::InvalidOperation));
+        Ok(())
+    }
+
+    #[test]
+    fn api_snapshot_serializes_to_stable_json() -> Result<(), Error> {
+        // --- Arrange ---
+        let alice = get_random_pk();
+        let bob = get_random_pk();
+        let build = |order: Vec<PublicKey>| -> Result<Accumulation, Error> {
+            let mut acc = Accumulation::new(Default::default(), Default::default());
+            for (i, account) in order.into_iter().enumerate() {
+                let added = acc.add_account(account, 1)?;
+                acc.apply(AccumulationEvent::AccountAdded(added))?;
+                let amount = Money::from_nano(if account == alice { 10 } else { 20 });
+                let e = acc.accumulate(vec![i as u8], hashmap![account => amount])?;
+                acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+            }
+            Ok(acc)
+        };
+        let first = build(vec![alice, bob])?;
+        let second = build(vec![bob, alice])?;
+
+        // --- Act ---
+        let snapshot = first.api_snapshot();
+        let json =
+            serde_json::to_string(&snapshot).map_err(|e| Error::NetworkOther(e.to_string()))?;
+
+        // --- Assert ---
+        assert_eq!(snapshot.version, API_SNAPSHOT_VERSION);
+        assert_eq!(snapshot.totals.accounts, 2);
+        assert_eq!(snapshot.totals.accumulated, Money::from_nano(30));
+        assert_eq!(snapshot.totals.rewarded_ids, 2);
+        let second_json = serde_json::to_string(&second.api_snapshot())
+            .map_err(|e| Error::NetworkOther(e.to_string()))?;
+        assert_eq!(json, second_json);
+        let value: serde_json::Value =
+            serde_json::from_str(&json).map_err(|e| Error::NetworkOther(e.to_string()))?;
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["accounts"].as_array().map(|a| a.len()), Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn when_claim_is_refunded_balance_returns() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim(account)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+        assert!(acc.get(&account).is_none());
+
+        // --- Act ---
+        let refund = acc.refund_claim(&claim)?;
+        acc.apply(AccumulationEvent::ClaimRefunded(refund))?;
+
+        // --- Assert ---
+        match acc.get(&account) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => {
+                assert_eq!(accumulated.reward, Money::from_nano(10));
+                assert_eq!(accumulated.work, 1);
+            }
+        };
+        assert_eq!(acc.total_claimed(), Money::zero());
+        acc.verify_invariant(Money::from_nano(10))?;
+        // The same claim can not be refunded twice.
+        assert_eq!(acc.refund_claim(&claim), Err(Error::DataExists));
+        Ok(())
+    }
+
+    #[test]
+    fn equal_claims_are_refunded_separately() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_inflation_factor(2.0)
+            .with_account_epoch_cap(Money::from_nano(10));
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let mut claims = vec![];
+        for _ in 0..2 {
+            let claim = acc.claim_amount(account, Money::from_nano(5))?;
+            acc.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+            claims.push(claim);
+        }
+
+        // --- Act ---
+        for claim in &claims {
+            let refund = acc.refund_claim(claim)?;
+            acc.apply(AccumulationEvent::ClaimRefunded(refund))?;
+        }
+
+        // --- Assert ---
+        // neither inflated nor limited by the epoch cap, and the work is left as it is
+        assert_eq!(
+            acc.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(10),
+                work: 1,
+            })
+        );
+        acc.verify_invariant(Money::from_nano(10))?;
+        Ok(())
+    }
+
+    #[test]
+    fn when_distribution_matches_mint_receipt_it_accumulates() -> Result<(), Error> {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let distribution = hashmap![get_random_pk() => Money::from_nano(10), get_random_pk() => Money::from_nano(5)];
+        let receipt = MintReceipt {
+            transaction: vec![1],
+            amount: Money::from_nano(15),
+        };
+        let e = acc.accumulate_with_receipt(vec![1], distribution, receipt)?;
+        assert_eq!(e.distribution.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn when_distribution_does_not_match_mint_receipt_it_is_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let distribution = hashmap![get_random_pk() => Money::from_nano(10), get_random_pk() => Money::from_nano(5)];
+        for minted in &[14, 16] {
+            let receipt = MintReceipt {
+                transaction: vec![1],
+                amount: Money::from_nano(*minted),
+            };
+            let result = acc.accumulate_with_receipt(vec![1], distribution.clone(), receipt);
+            assert_eq!(result, Err(Error::InvalidOperation));
+        }
+    }
+
+    #[test]
+    fn mint_receipt_is_matched_against_inflated_distribution() {
+        let acc =
+            Accumulation::new(Default::default(), Default::default()).with_inflation_factor(2.0);
+        let distribution = hashmap![get_random_pk() => Money::from_nano(10), get_random_pk() => Money::from_nano(5)];
+        for (minted, accepted) in &[(15, false), (30, true)] {
+            let receipt = MintReceipt {
+                transaction: vec![1],
+                amount: Money::from_nano(*minted),
+            };
+            let result = acc.accumulate_with_receipt(vec![1], distribution.clone(), receipt);
+            assert_eq!(result.is_ok(), *accepted);
+        }
+    }
+
+    #[test]
+    fn buffered_rewards_flush_to_one_event_preserving_totals() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let alice = get_random_pk();
+        let bob = get_random_pk();
+        acc.buffer(
+            vec![1],
+            hashmap![alice => Money::from_nano(1), bob => Money::from_nano(2)],
+        )?;
+        acc.buffer(vec![2], hashmap![alice => Money::from_nano(3)])?;
+        acc.buffer(vec![3], hashmap![bob => Money::from_nano(4)])?;
+        assert_eq!(
+            acc.buffer(vec![3], hashmap![bob => Money::from_nano(4)]),
+            Err(Error::DataExists)
+        );
+
+        // --- Act ---
+        let e = acc.flush_accumulated()?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(e.distribution.len(), 2);
+        assert_eq!(e.coalesced, vec![vec![1], vec![2], vec![3]]);
+        assert_eq!(acc.get(&alice).map(|a| a.reward), Some(Money::from_nano(4)));
+        assert_eq!(acc.get(&bob).map(|a| a.reward), Some(Money::from_nano(6)));
+        // The buffered ids are rewarded, and the buffer is empty.
+        assert_eq!(
+            acc.accumulate(vec![2], hashmap![alice => Money::from_nano(3)]),
+            Err(Error::DataExists)
+        );
+        assert_eq!(acc.flush_accumulated(), Err(Error::NoSuchData));
+        Ok(())
+    }
+
+    #[test]
+    fn inflation_factor_scales_distribution() -> Result<(), Error> {
+        let account = get_random_pk();
+        let distribution = hashmap![account => Money::from_nano(10)];
+        for (factor, expected) in &[(1.0, 10), (1.5, 15)] {
+            let acc = Accumulation::new(Default::default(), Default::default())
+                .with_inflation_factor(*factor);
+            let e = acc.accumulate(vec![1], distribution.clone())?;
+            assert_eq!(e.distribution[&account], Money::from_nano(*expected));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn when_inflation_overflows_accumulation_is_rejected() {
+        let acc =
+            Accumulation::new(Default::default(), Default::default()).with_inflation_factor(2.0);
+        let distribution = hashmap![get_random_pk() => Money::from_nano(u64::MAX / 2 + 1)];
+        let result = acc.accumulate(vec![1], distribution);
+        assert_eq!(result, Err(Error::ExcessiveValue));
+    }
+
+    #[test]
+    fn reward_velocity_sums_rewards_over_recent_epochs() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        // Rewards of 1, 2 and 4 nanos in three consecutive epochs.
+        for (epoch, amount) in [1, 2, 4].iter().enumerate() {
+            if epoch > 0 {
+                acc.advance_epoch();
+            }
+            let e = acc.accumulate(
+                vec![epoch as u8],
+                hashmap![account => Money::from_nano(*amount)],
+            )?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+
+        // --- Act + Assert ---
+        assert_eq!(acc.reward_velocity(&account, 0), Money::zero());
+        assert_eq!(acc.reward_velocity(&account, 1), Money::from_nano(4));
+        assert_eq!(acc.reward_velocity(&account, 2), Money::from_nano(6));
+        assert_eq!(acc.reward_velocity(&account, 3), Money::from_nano(7));
+        assert_eq!(acc.reward_velocity(&account, 10), Money::from_nano(7));
+        acc.advance_epoch();
+        assert_eq!(acc.reward_velocity(&account, 1), Money::zero());
+        assert_eq!(acc.reward_velocity(&get_random_pk(), 3), Money::zero());
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_balances_load_without_rewarded_ids() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let counter = RewardCounter {
+            reward: Money::from_nano(10),
+            work: 4,
+        };
+
+        // --- Act ---
+        let mut acc = Accumulation::from_legacy(hashmap![account => counter.clone()]);
+
+        // --- Assert ---
+        assert_eq!(acc.get(&account), Some(&counter));
+        assert!(acc.compact_idempotency() == crate::compact::encode_ids(&Vec::new()));
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(15))
+        );
+        let claim = acc.claim(account)?;
+        assert_eq!(claim.rewards.work, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn rewards_to_young_accounts_are_escrowed() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_escrow_until_age(2);
+        let account = get_random_pk();
+        let added = acc.add_account(account, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+
+        // --- Act ---
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        assert_eq!(acc.escrowed(&account), Money::from_nano(10));
+        assert_eq!(acc.get(&account).map(|a| a.reward), Some(Money::zero()));
+        assert!(acc
+            .accumulate(vec![1], hashmap![account => Money::from_nano(10)])
+            .is_err());
+        assert!(acc.release_escrow(acc.epoch()).released.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn escrow_is_released_after_maturity() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_escrow_until_age(2);
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        acc.advance_epoch();
+        assert!(acc.release_escrow(acc.epoch()).released.is_empty());
+        acc.advance_epoch();
+
+        // --- Act ---
+        let released = acc.release_escrow(acc.epoch());
+        acc.apply(AccumulationEvent::EscrowReleased(released.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(released.released, hashmap![account => Money::from_nano(10)]);
+        assert_eq!(acc.escrowed(&account), Money::zero());
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(10))
+        );
+
+        // rewards after maturity are credited directly
+        let e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(15))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn audit_of_healthy_state_finds_nothing() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_account_epoch_cap(Money::from_nano(10));
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(10), bob => Money::from_nano(3)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        acc.buffer(vec![2], hashmap![bob => Money::from_nano(1)])?;
+
+        // --- Act ---
+        let report = acc.audit();
+
+        // --- Assert ---
+        assert!(report.is_healthy());
+        assert_eq!(report.total, Some(Money::from_nano(13)));
+        Ok(())
+    }
+
+    #[test]
+    fn audit_finds_planted_inconsistencies() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_account_epoch_cap(Money::from_nano(10));
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(vec![1], hashmap![alice => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let _ = acc.idempotency.remove(&vec![1]);
+        let _ = acc.epoch_rewards.insert(alice, Money::from_nano(11));
+        let _ = acc.accumulated.insert(
+            bob,
+            RewardCounter {
+                reward: Money::from_nano(u64::MAX),
+                work: 1,
+            },
+        );
+
+        // --- Act ---
+        let report = acc.audit();
+
+        // --- Assert ---
+        assert!(!report.is_healthy());
+        assert_eq!(report.total, None);
+        assert_eq!(report.over_cap, vec![alice]);
+        assert_eq!(report.unrecorded_ids, vec![vec![1]]);
+        Ok(())
+    }
+
+    #[test]
+    fn staged_events_are_applied_on_commit() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let added = acc.add_account(account, 1)?;
+        let first = acc.accumulate(vec![1], hashmap![account => Money::from_nano(2)])?;
+        let second = acc.accumulate(vec![2], hashmap![account => Money::from_nano(3)])?;
+        acc.stage(AccumulationEvent::AccountAdded(added));
+        acc.stage(AccumulationEvent::RewardsAccumulated(first));
+        acc.stage(AccumulationEvent::RewardsAccumulated(second));
+        assert_eq!(acc.staged().len(), 3);
+        assert!(acc.get(&account).is_none());
+
+        // --- Act ---
+        acc.commit_staged()?;
+
+        // --- Assert ---
+        assert!(acc.staged().is_empty());
+        assert_eq!(
+            acc.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(5),
+                work: 3,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn discarded_events_are_not_applied() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(2)])?;
+        acc.stage(AccumulationEvent::RewardsAccumulated(e));
+
+        // --- Act ---
+        acc.discard_staged();
+        acc.commit_staged()?;
+
+        // --- Assert ---
+        assert!(acc.staged().is_empty());
+        assert!(acc.get(&account).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_staged_events_are_not_committed() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(2)])?;
+        acc.stage(AccumulationEvent::RewardsAccumulated(e.clone()));
+        acc.stage(AccumulationEvent::RewardsAccumulated(e));
+
+        // --- Act ---
+        let result = acc.commit_staged();
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::DataExists));
+        assert_eq!(acc.staged().len(), 2);
+        assert!(acc.get(&account).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn accounts_can_be_added_up_to_the_limit() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_max_accounts(2);
+        let (alice, bob, carol) = (get_random_pk(), get_random_pk(), get_random_pk());
+        let added = acc.add_account(alice, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(1), bob => Money::from_nano(1)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let added = acc.add_account(carol, 1);
+        let accumulated = acc.accumulate(vec![2], hashmap![carol => Money::from_nano(1)]);
+
+        // --- Assert ---
+        assert_eq!(added, Err(Error::TooManyEntries));
+        assert_eq!(accumulated, Err(Error::TooManyEntries));
+        assert!(acc
+            .accumulate(vec![2], hashmap![alice => Money::from_nano(1)])
+            .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn implicit_accounts_beyond_the_limit_are_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default()).with_max_accounts(1);
+        let result = acc.accumulate(
+            vec![1],
+            hashmap![get_random_pk() => Money::from_nano(1), get_random_pk() => Money::from_nano(1)],
+        );
+        assert_eq!(result, Err(Error::TooManyEntries));
+    }
+
+    #[test]
+    fn escrowed_recipients_count_towards_the_limit() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_max_accounts(1)
+            .with_escrow_until_age(1);
+        let (a, b) = (get_random_pk(), get_random_pk());
+
+        // --- Act ---
+        let both = acc.accumulate(
+            vec![1],
+            hashmap![a => Money::from_nano(1), b => Money::from_nano(1)],
+        );
+        let e = acc.accumulate(vec![1], hashmap![a => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        assert_eq!(both, Err(Error::TooManyEntries));
+        assert_eq!(
+            acc.accumulate(vec![2], hashmap![b => Money::from_nano(1)]),
+            Err(Error::TooManyEntries)
+        );
+        assert!(acc
+            .accumulate(vec![2], hashmap![a => Money::from_nano(1)])
+            .is_ok());
+        assert!(acc.add_account(a, 1).is_ok());
+        acc.advance_epoch();
+        let released = acc.release_escrow(acc.epoch());
+        acc.apply(AccumulationEvent::EscrowReleased(released))?;
+        assert_eq!(acc.get_all().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn partial_claim_leaves_remaining_balance_and_work() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        for id in 0..2 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(5)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+
+        // --- Act ---
+        let claim = acc.claim_amount(account, Money::from_nano(4))?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(claim.rewards.reward, Money::from_nano(4));
+        assert_eq!(
+            acc.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(6),
+                work: 2,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn claiming_more_than_balance_is_rejected() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let result = acc.claim_amount(account, Money::from_nano(6));
+        assert_eq!(result, Err(Error::ExcessiveValue));
+        assert_eq!(
+            acc.claim_amount(get_random_pk(), Money::zero()),
+            Err(Error::NoSuchKey)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn claiming_the_remaining_balance_removes_the_account() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim_amount(account, Money::from_nano(2))?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+
+        // --- Act ---
+        let claim = acc.claim_amount(account, Money::from_nano(3))?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+
+        // --- Assert ---
+        assert!(acc.get(&account).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn overflowing_event_is_rejected_by_apply() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let overflowing = RewardsAccumulated {
+            id: vec![2],
+            distribution: hashmap![account => Money::from_nano(u64::MAX)],
+            fingerprint: None,
+            reward_id: 2,
+            coalesced: vec![],
+            kind: RewardKind::Farming,
+            epoch: 0,
+            seq: 1,
+        };
+
+        // --- Act ---
+        let result = acc.apply(AccumulationEvent::RewardsAccumulated(overflowing));
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::ExcessiveValue));
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(1))
+        );
+        assert!(acc
+            .accumulate(vec![2], hashmap![account => Money::from_nano(1)])
+            .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn state_is_rebuilt_from_events() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let mut events = vec![];
+        let e = AccumulationEvent::AccountAdded(acc.add_account(alice, 2)?);
+        acc.apply(e.clone())?;
+        events.push(e);
+        let e = AccumulationEvent::RewardsAccumulated(acc.accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(3), bob => Money::from_nano(4)],
+        )?);
+        acc.apply(e.clone())?;
+        events.push(e);
+        let e = AccumulationEvent::RewardsClaimed(acc.claim(bob)?);
+        acc.apply(e.clone())?;
+        events.push(e);
+
+        // --- Act ---
+        let rebuilt = Accumulation::from_events(events)?;
+
+        // --- Assert ---
+        assert_eq!(rebuilt.get_all(), acc.get_all());
+        assert_eq!(rebuilt.idempotency, acc.idempotency);
+        assert_eq!(
+            rebuilt.accumulate(vec![1], Default::default()),
+            Err(Error::DataExists)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rebuilding_from_overflowing_events_fails() {
+        let account = get_random_pk();
+        let events = (0..2).map(|i| {
+            AccumulationEvent::RewardsAccumulated(RewardsAccumulated {
+                id: vec![i],
+                distribution: hashmap![account => Money::from_nano(u64::MAX)],
+                fingerprint: None,
+                reward_id: u64::from(i) + 1,
+                coalesced: vec![],
+                kind: RewardKind::Farming,
+                epoch: 0,
+                seq: u64::from(i),
+            })
+        });
+        let result = Accumulation::from_events(events);
+        assert!(matches!(result, Err(Error::ExcessiveValue)));
+    }
+
+    #[test]
+    fn pruned_ids_can_be_accumulated_again() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        for id in 0..3 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(1)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+
+        // --- Act ---
+        acc.prune_idempotency(2);
+
+        // --- Assert ---
+        let distribution = hashmap![account => Money::from_nano(1)];
+        assert!(acc.accumulate(vec![0], distribution.clone()).is_ok());
+        assert_eq!(
+            acc.accumulate(vec![1], distribution.clone()),
+            Err(Error::DataExists)
+        );
+        assert_eq!(
+            acc.accumulate(vec![2], distribution),
+            Err(Error::DataExists)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn idempotency_cap_keeps_most_recent_ids() -> Result<(), Error> {
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_idempotency_cap(1);
+        let account = get_random_pk();
+        for id in 0..2 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(1)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        let distribution = hashmap![account => Money::from_nano(1)];
+        assert!(acc.accumulate(vec![0], distribution.clone()).is_ok());
+        assert_eq!(
+            acc.accumulate(vec![1], distribution),
+            Err(Error::DataExists)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn id_is_rewarded_once_applied() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let id = vec![1, 2, 3];
+        let e = acc.accumulate(id.clone(), hashmap![get_random_pk() => Money::from_nano(1)])?;
+        assert!(!acc.is_rewarded(&id));
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert!(acc.is_rewarded(&id));
+        Ok(())
+    }
+
+    #[test]
+    fn total_accumulated_drops_after_claim() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (alice, bob, carol) = (get_random_pk(), get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(1), bob => Money::from_nano(2), carol => Money::from_nano(3)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(acc.total_accumulated()?, Money::from_nano(6));
+
+        // --- Act ---
+        let claim = acc.claim(bob)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+
+        // --- Assert ---
+        assert_eq!(acc.total_accumulated()?, Money::from_nano(4));
+        Ok(())
+    }
+
+    #[test]
+    fn total_accumulated_overflow_is_an_error() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![get_random_pk() => Money::from_nano(u64::MAX), get_random_pk() => Money::from_nano(1)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(acc.total_accumulated(), Err(Error::ExcessiveValue));
+        Ok(())
+    }
+
+    #[test]
+    fn batch_overflowing_across_entries_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![0], hashmap![account => Money::from_nano(u64::MAX - 5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let entries = vec![
+            (vec![1], hashmap![account => Money::from_nano(3)]),
+            (vec![2], hashmap![account => Money::from_nano(3)]),
+        ];
+        assert!(acc.accumulate(vec![2], entries[1].1.clone()).is_ok());
+
+        // --- Act ---
+        let result = acc.accumulate_batch(entries, false);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::ExcessiveValue));
+        assert!(!acc.is_rewarded(&vec![1]));
+        Ok(())
+    }
+
+    #[test]
+    fn empty_distribution_is_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let result = acc.accumulate(vec![1], Default::default());
+        assert_eq!(result, Err(Error::InvalidOperation));
+        assert!(!acc.is_rewarded(&vec![1]));
+    }
+
+    #[test]
+    fn zero_amount_is_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let result = acc.accumulate(vec![1], hashmap![get_random_pk() => Money::zero()]);
+        assert_eq!(result, Err(Error::InvalidOperation));
+        assert!(!acc.is_rewarded(&vec![1]));
+    }
+
+    #[test]
+    fn work_increments_once_per_accumulation() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let added = acc.add_account(account, 5)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+
+        // --- Act ---
+        for id in 0..2 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(10)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+
+        // --- Assert ---
+        assert_eq!(
+            acc.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(20),
+                work: 7,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unregistered_recipient_is_created_by_default() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert!(acc.get(&account).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn unregistered_recipient_is_rejected_when_registration_required() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_registration_required();
+        let (registered, unregistered) = (get_random_pk(), get_random_pk());
+        let added = acc.add_account(registered, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+
+        // --- Act ---
+        let result = acc.accumulate(
+            vec![1],
+            hashmap![registered => Money::from_nano(1), unregistered => Money::from_nano(1)],
+        );
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::NoSuchKey));
+        assert!(acc
+            .accumulate(vec![1], hashmap![registered => Money::from_nano(1)])
+            .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn resolved_recipients_count_as_registered() -> Result<(), Error> {
+        let acc =
+            Accumulation::new(Default::default(), Default::default()).with_registration_required();
+        let account = get_random_pk();
+        let (added, _) = acc.accumulate_with_resolver(
+            vec![1],
+            hashmap![account => Money::from_nano(1)],
+            |_| Some(1),
+        )?;
+        assert_eq!(added.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_bincode() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_escrow_until_age(1);
+        let (alice, bob, carol) = (get_random_pk(), get_random_pk(), get_random_pk());
+        for id in 0..3 {
+            let e = acc.accumulate(
+                vec![id],
+                hashmap![alice => Money::from_nano(1), bob => Money::from_nano(2)],
+            )?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        acc.advance_epoch();
+        let e = AccumulationEvent::EscrowReleased(acc.release_escrow(acc.epoch()));
+        acc.apply(e)?;
+        let claim = acc.claim(bob)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        let e = acc.accumulate(vec![3], hashmap![carol => Money::from_nano(4)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let bytes = bincode::serialize(&acc.snapshot()).unwrap();
+        let snapshot: AccumulationSnapshot = bincode::deserialize(&bytes).unwrap();
+        let restored = Accumulation::from_snapshot(snapshot).with_escrow_until_age(1);
+
+        // --- Assert ---
+        assert_eq!(restored.get_all(), acc.get_all());
+        assert_eq!(restored.idempotency, acc.idempotency);
+        assert_eq!(restored.snapshot(), acc.snapshot());
+        assert_eq!(restored.escrowed(&carol), Money::from_nano(4));
+        assert_eq!(restored.state_hash()?, acc.state_hash()?);
+        // alice joined in the first epoch, so is no longer escrowed
+        let e = restored.accumulate(vec![4], hashmap![alice => Money::from_nano(1)])?;
+        assert_eq!(restored.credited(&e.distribution, e.epoch)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn escrow_is_part_of_state_hash() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_escrow_until_age(1);
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let mut more_escrowed = acc.clone();
+        let _ = more_escrowed.escrowed.insert(account, Money::from_nano(6));
+        let mut joined_later = acc.clone();
+        let _ = joined_later.joined.insert(account, 1);
+
+        // --- Act + Assert ---
+        assert_ne!(acc.state_hash()?, more_escrowed.state_hash()?);
+        assert_ne!(acc.state_hash()?, joined_later.state_hash()?);
+        Ok(())
+    }
+
+    #[test]
+    fn transfer_moves_rewards_and_work() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (old_key, new_key) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(vec![1], hashmap![old_key => Money::from_nano(7)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let e = acc.transfer(old_key, new_key)?;
+        acc.apply(AccumulationEvent::RewardsTransferred(e))?;
+
+        // --- Assert ---
+        assert!(acc.get(&old_key).is_none());
+        assert_eq!(
+            acc.get(&new_key),
+            Some(&RewardCounter {
+                reward: Money::from_nano(7),
+                work: 1,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn transfer_from_absent_account_is_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let result = acc.transfer(get_random_pk(), get_random_pk());
+        assert_eq!(result, Err(Error::NoSuchKey));
+    }
+
+    #[test]
+    fn transfer_to_existing_account_is_rejected() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (from, to) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![from => Money::from_nano(1), to => Money::from_nano(1)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(acc.transfer(from, to), Err(Error::BalanceExists));
+        Ok(())
+    }
+
+    #[test]
+    fn ordered_is_independent_of_insertion_order() -> Result<(), Error> {
+        let accounts: Vec<_> = (0..5).map(|_| get_random_pk()).collect();
+        let build =
+            |accounts: &mut dyn Iterator<Item = &PublicKey>| -> Result<Accumulation, Error> {
+                let mut acc = Accumulation::new(Default::default(), Default::default());
+                for account in accounts {
+                    let added = acc.add_account(*account, 1)?;
+                    acc.apply(AccumulationEvent::AccountAdded(added))?;
+                }
+                Ok(acc)
+            };
+        let forward = build(&mut accounts.iter())?;
+        let backward = build(&mut accounts.iter().rev())?;
+        assert_eq!(forward.ordered(), backward.ordered());
+        let ids: Vec<_> = forward.ordered().into_iter().map(|(id, _)| *id).collect();
+        let mut sorted = accounts.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+        Ok(())
+    }
+
+    #[test]
+    fn equal_states_hash_equally() -> Result<(), Error> {
+        // --- Arrange ---
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let entries = [
+            (vec![1], hashmap![alice => Money::from_nano(1)]),
+            (vec![2], hashmap![bob => Money::from_nano(2)]),
+        ];
+        let build = |entries: &mut dyn Iterator<Item = &(Vec<u8>, HashMap<PublicKey, Money>)>| -> Result<Accumulation, Error> {
+            let mut acc = Accumulation::new(Default::default(), Default::default());
+            for (id, distribution) in entries {
+                let e = acc.accumulate(id.clone(), distribution.clone())?;
+                acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+            }
+            Ok(acc)
+        };
+
+        // --- Act ---
+        let forward = build(&mut entries.iter())?;
+        let backward = build(&mut entries.iter().rev())?;
+
+        // --- Assert ---
+        assert_eq!(forward.state_hash()?, backward.state_hash()?);
+        Ok(())
+    }
+
+    #[test]
+    fn one_nano_difference_changes_the_hash() -> Result<(), Error> {
+        let account = get_random_pk();
+        let first = Accumulation::new(
+            Default::default(),
+            hashmap![account => RewardCounter { reward: Money::from_nano(10), work: 1 }],
+        );
+        let second = Accumulation::new(
+            Default::default(),
+            hashmap![account => RewardCounter { reward: Money::from_nano(11), work: 1 }],
+        );
+        assert_ne!(first.state_hash()?, second.state_hash()?);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_of_disjoint_accounts_keeps_both() -> Result<(), Error> {
+        // --- Arrange ---
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let mut ours = Accumulation::new(Default::default(), Default::default());
+        let e = ours.accumulate(vec![1], hashmap![alice => Money::from_nano(1)])?;
+        ours.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let mut theirs = Accumulation::new(Default::default(), Default::default());
+        let e = theirs.accumulate(vec![2], hashmap![bob => Money::from_nano(2)])?;
+        theirs.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let merged = ours.merge(theirs)?;
+
+        // --- Assert ---
+        assert_eq!(
+            merged.get(&alice).map(|a| a.reward),
+            Some(Money::from_nano(1))
+        );
+        assert_eq!(
+            merged.get(&bob).map(|a| a.reward),
+            Some(Money::from_nano(2))
+        );
+        assert!(merged.is_rewarded(&vec![1]));
+        assert!(merged.is_rewarded(&vec![2]));
+        Ok(())
+    }
+
+    #[test]
+    fn merge_of_overlapping_accounts_sums_them() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let mut ours = Accumulation::new(Default::default(), Default::default());
+        let e = ours.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        ours.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let mut theirs = Accumulation::new(Default::default(), Default::default());
+        for id in 2..4 {
+            let e = theirs.accumulate(vec![id], hashmap![account => Money::from_nano(2)])?;
+            theirs.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+
+        // --- Act ---
+        let merged = ours.merge(theirs)?;
+
+        // --- Assert ---
+        assert_eq!(
+            merged.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(5),
+                work: 3,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn merge_overflowing_an_account_fails() {
+        let account = get_random_pk();
+        let counter = RewardCounter {
+            reward: Money::from_nano(u64::MAX),
+            work: 1,
+        };
+        let ours = Accumulation::new(Default::default(), hashmap![account => counter.clone()]);
+        let theirs = Accumulation::new(Default::default(), hashmap![account => counter]);
+        assert!(matches!(ours.merge(theirs), Err(Error::ExcessiveValue)));
+    }
+
+    #[test]
+    fn merge_keeps_ids_of_idempotency_filter() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let filtered = || -> Result<Accumulation, Error> {
+            Ok(Accumulation::new(Default::default(), Default::default())
+                .with_idempotency_filter(BloomFilter::new(100, 0.01)?))
+        };
+        let ours = filtered()?;
+        let mut theirs = filtered()?;
+        let e = theirs.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        theirs.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let unfiltered = Accumulation::new(Default::default(), Default::default());
+
+        // --- Act ---
+        let merged = ours.merge(theirs.clone())?;
+
+        // --- Assert ---
+        assert!(merged.is_rewarded(&vec![1]));
+        assert!(matches!(
+            unfiltered.merge(theirs),
+            Err(Error::InvalidOperation)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn merging_a_split_preserves_pool_escrow_and_claimed() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_reward_pool(Money::from_nano(100))
+            .with_escrow_until_age(1);
+        let (alice, carol) = (get_random_pk(), get_random_pk());
+        let added = acc.add_account(alice, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+        acc.advance_epoch();
+        let e = acc.accumulate(vec![1], hashmap![alice => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let e = acc.accumulate(vec![2], hashmap![carol => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim(alice)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+
+        // --- Act ---
+        let (matching, rest) = acc.split(|id| *id == alice);
+        let (rest_pool, rest_claimed) = (rest.pool_balance(), rest.total_claimed());
+        let (rest_escrowed, matching_escrowed) = (rest.escrowed(&carol), matching.escrowed(&carol));
+        let merged = matching.merge(rest)?;
+
+        // --- Assert ---
+        assert_eq!(rest_pool, Some(Money::zero()));
+        assert_eq!(rest_claimed, Money::zero());
+        assert_eq!(rest_escrowed, Money::from_nano(5));
+        assert_eq!(matching_escrowed, Money::zero());
+        assert_eq!(merged.pool_balance(), Some(Money::from_nano(85)));
+        assert_eq!(merged.total_claimed(), Money::from_nano(10));
+        assert_eq!(merged.escrowed(&carol), Money::from_nano(5));
+        assert_eq!(merged.joined.get(&carol), Some(&1));
+        merged.verify_invariant(Money::from_nano(15))?;
+        Ok(())
+    }
+
+    #[test]
+    fn split_partitions_accounts_and_keeps_rewarded_ids() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(1), bob => Money::from_nano(2)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let (matching, rest) = acc.split(|id| *id == alice);
+
+        // --- Assert ---
+        assert_eq!(
+            matching.get(&alice).map(|a| a.reward),
+            Some(Money::from_nano(1))
+        );
+        assert!(matching.get(&bob).is_none());
+        assert_eq!(rest.get(&bob).map(|a| a.reward), Some(Money::from_nano(2)));
+        assert!(rest.get(&alice).is_none());
+        for half in &[matching, rest] {
+            let result = half.accumulate(vec![1], hashmap![alice => Money::from_nano(1)]);
+            assert_eq!(result, Err(Error::DataExists));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn accounts_at_least_threshold_are_returned_highest_first() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (below, equal, above) = (get_random_pk(), get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![below => Money::from_nano(9), equal => Money::from_nano(10), above => Money::from_nano(11)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let accounts = acc.accounts_at_least(Money::from_nano(10));
+
+        // --- Assert ---
+        assert_eq!(
+            accounts,
+            vec![(above, Money::from_nano(11)), (equal, Money::from_nano(10))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate_checks_account_added() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let added = AccumulationEvent::AccountAdded(acc.add_account(get_random_pk(), 1)?);
+        acc.validate(&added)?;
+        acc.apply(added.clone())?;
+        assert_eq!(acc.validate(&added), Err(Error::BalanceExists));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_checks_rewards_accumulated() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = AccumulationEvent::RewardsAccumulated(
+            acc.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?,
+        );
+        acc.validate(&e)?;
+        acc.apply(e.clone())?;
+        assert_eq!(acc.validate(&e), Err(Error::DataExists));
+        let overflowing = AccumulationEvent::RewardsAccumulated(RewardsAccumulated {
+            id: vec![2],
+            distribution: hashmap![account => Money::from_nano(u64::MAX)],
+            fingerprint: None,
+            reward_id: 2,
+            coalesced: vec![],
+            kind: RewardKind::Farming,
+            epoch: 0,
+            seq: 1,
+        });
+        assert_eq!(acc.validate(&overflowing), Err(Error::ExcessiveValue));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_checks_rewards_claimed() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = AccumulationEvent::RewardsClaimed(acc.claim(account)?);
+        acc.validate(&claim)?;
+        acc.apply(claim.clone())?;
+        assert_eq!(acc.validate(&claim), Err(Error::NoSuchKey));
+        Ok(())
+    }
+
+    fn assert_same_state(acc: &Accumulation, before: &Accumulation, accounts: &[PublicKey]) {
+        assert_eq!(acc.snapshot(), before.snapshot());
+        assert_eq!(acc.pool_balance(), before.pool_balance());
+        assert_eq!(acc.last_reward_id(), before.last_reward_id());
+        for account in accounts {
+            assert_eq!(acc.statement(account), before.statement(account));
+            assert_eq!(acc.generation(account), before.generation(account));
+            assert_eq!(acc.epoch_rewards(account), before.epoch_rewards(account));
+        }
+    }
+
+    #[test]
+    fn undo_of_account_added_removes_it() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let before = acc.clone();
+        let added = acc.add_account(account, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+        acc.undo_last()?;
+        assert_same_state(&acc, &before, &[account]);
+        assert!(acc.get(&account).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn undo_of_accumulation_subtracts_it() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_reward_pool(Money::from_nano(100));
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(vec![1], hashmap![alice => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let before = acc.clone();
+
+        // --- Act ---
+        let e = acc.accumulate_unique(
+            vec![2],
+            hashmap![alice => Money::from_nano(2), bob => Money::from_nano(3)],
+            vec![9],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        acc.undo_last()?;
+
+        // --- Assert ---
+        assert_same_state(&acc, &before, &[alice, bob]);
+        assert!(!acc.is_rewarded(&vec![2]));
+        assert!(acc.is_rewarded(&vec![1]));
+        assert!(acc
+            .accumulate_unique(vec![2], hashmap![alice => Money::from_nano(2)], vec![9])
+            .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn undo_of_claim_restores_the_balance() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let before = acc.clone();
+
+        // --- Act ---
+        let claim = acc.claim(account)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        acc.undo_last()?;
+
+        // --- Assert ---
+        assert_same_state(&acc, &before, &[account]);
+        assert_eq!(
+            acc.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(5),
+                work: 1,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn undo_of_transfer_moves_back() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (from, to) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(vec![1], hashmap![from => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let before = acc.clone();
+        let e = acc.transfer(from, to)?;
+        acc.apply(AccumulationEvent::RewardsTransferred(e))?;
+        acc.undo_last()?;
+        assert_same_state(&acc, &before, &[from, to]);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_is_bounded() -> Result<(), Error> {
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        assert_eq!(acc.undo_last(), Err(Error::NoSuchData));
+        let account = get_random_pk();
+        for id in 0..RETAINED_UNDO + 1 {
+            let e = acc.accumulate(vec![id as u8], hashmap![account => Money::from_nano(1)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        for _ in 0..RETAINED_UNDO {
+            acc.undo_last()?;
+        }
+        assert_eq!(acc.undo_last(), Err(Error::NoSuchData));
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(1))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn overflow_near_max_is_rejected_in_checked_mode() -> Result<(), Error> {
+        let account = get_random_pk();
+        let counter = RewardCounter {
+            reward: Money::from_nano(u64::MAX - 1),
+            work: 1,
+        };
+        let acc = Accumulation::new(Default::default(), hashmap![account => counter]);
+        assert_eq!(acc.overflow_mode(), OverflowMode::Checked);
+        let result = acc.accumulate(vec![1], hashmap![account => Money::from_nano(2)]);
+        assert_eq!(result, Err(Error::ExcessiveValue));
+        Ok(())
+    }
+
+    #[test]
+    fn overflow_near_max_saturates_in_saturating_mode() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let counter = RewardCounter {
+            reward: Money::from_nano(u64::MAX - 1),
+            work: 1,
+        };
+        let mut acc = Accumulation::new(Default::default(), hashmap![account => counter])
+            .with_overflow_mode(OverflowMode::Saturating);
+
+        // --- Act ---
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(2)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        assert_eq!(
+            acc.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(u64::MAX),
+                work: 2,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn claim_to_destination_removes_the_source() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (account, cold_wallet) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let claim = acc.claim_to(account, cold_wallet)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(claim.account, account);
+        assert_eq!(claim.destination, cold_wallet);
+        assert_eq!(claim.rewards.reward, Money::from_nano(5));
+        assert!(acc.get(&account).is_none());
+        assert!(acc.get(&cold_wallet).is_none());
+        let e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        assert_eq!(acc.claim(account)?.destination, account);
+        Ok(())
+    }
+
+    #[test]
+    fn metrics_summarize_balances() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let empty = acc.metrics();
+        assert_eq!(empty.accounts, 0);
+        assert_eq!(empty.total, Some(Money::zero()));
+        assert_eq!(empty.mean_balance, Money::zero());
+        assert_eq!(empty.min_balance, Money::zero());
+        let (a, b, c) = (get_random_pk(), get_random_pk(), get_random_pk());
+        let distribution = hashmap![
+            a => Money::from_nano(1),
+            b => Money::from_nano(4),
+            c => Money::from_nano(6)
+        ];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let metrics = acc.metrics();
+
+        // --- Assert ---
+        assert_eq!(metrics.accounts, 3);
+        assert_eq!(metrics.total, Some(Money::from_nano(11)));
+        assert_eq!(metrics.rewarded_ids, 1);
+        assert_eq!(metrics.max_balance, Money::from_nano(6));
+        assert_eq!(metrics.min_balance, Money::from_nano(1));
+        assert_eq!(metrics.mean_balance, Money::from_nano(3));
+        Ok(())
+    }
+
+    #[test]
+    fn claims_across_a_threshold_halve_the_reward_multiplier() -> Result<(), Error> {
+        // --- Arrange ---
+        let schedule = RewardSchedule::new(vec![Money::from_nano(10)]);
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_reward_schedule(schedule.clone());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(6), b => Money::from_nano(6)];
+        let mut events = vec![AccumulationEvent::RewardsAccumulated(
+            acc.accumulate(vec![1], distribution)?,
+        )];
+        acc.apply(events[0].clone())?;
+
+        // --- Act ---
+        for account in &[a, b] {
+            assert_eq!(acc.reward_schedule().current_multiplier(), 1.0);
+            let claim = AccumulationEvent::RewardsClaimed(acc.claim(*account)?);
+            acc.apply(claim.clone())?;
+            events.push(claim);
+        }
+
+        // --- Assert ---
+        assert_eq!(acc.reward_schedule().claimed(), Money::from_nano(12));
+        assert_eq!(acc.reward_schedule().current_multiplier(), 0.5);
+        let replayed = Accumulation::from_events(events)?.with_reward_schedule(schedule);
+        assert_eq!(replayed.reward_schedule(), acc.reward_schedule());
+        acc.undo_last()?;
+        assert_eq!(acc.reward_schedule().current_multiplier(), 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn closed_account_forfeits_its_balance_to_another_account() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(5), b => Money::from_nano(7)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let to_b = acc.close_account(a, Some(b))?;
+        acc.apply(AccumulationEvent::AccountClosed(to_b.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(to_b.forfeited, Money::from_nano(5));
+        assert!(acc.get(&a).is_none());
+        assert_eq!(acc.get(&b).map(|b| b.reward), Some(Money::from_nano(12)));
+        assert_eq!(acc.total_accumulated()?, Money::from_nano(12));
+        Ok(())
+    }
+
+    #[test]
+    fn closed_account_without_forfeit_to_drops_its_balance() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(5), b => Money::from_nano(7)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let dropped = acc.close_account(a, None)?;
+        acc.apply(AccumulationEvent::AccountClosed(dropped.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(dropped.forfeited, Money::from_nano(5));
+        assert!(acc.get(&a).is_none());
+        assert_eq!(acc.get(&b).map(|b| b.reward), Some(Money::from_nano(7)));
+        assert_eq!(acc.total_accumulated()?, Money::from_nano(7));
+        Ok(())
+    }
+
+    #[test]
+    fn closing_absent_account_is_rejected() {
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let result = acc.close_account(get_random_pk(), None);
+        assert_eq!(result, Err(Error::NoSuchKey));
+    }
+
+    #[test]
+    fn closing_account_is_undone() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(5), b => Money::from_nano(7)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let before = acc.get_all().clone();
+        let to_b = acc.close_account(a, Some(b))?;
+        acc.apply(AccumulationEvent::AccountClosed(to_b))?;
+
+        // --- Act ---
+        acc.undo_last()?;
+
+        // --- Assert ---
+        assert_eq!(acc.get_all(), &before);
+        Ok(())
+    }
+
+    #[test]
+    fn escrowed_rewards_are_forfeited_with_the_balance() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_escrow_until_age(1);
+        let (a, b) = (get_random_pk(), get_random_pk());
+        for (account, work) in &[(a, 1), (b, 1)] {
+            let added = acc.add_account(*account, *work)?;
+            acc.apply(AccumulationEvent::AccountAdded(added))?;
+        }
+        let e = acc.accumulate(vec![1], hashmap![a => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let to_b = acc.close_account(a, Some(b))?;
+        acc.apply(AccumulationEvent::AccountClosed(to_b.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(to_b.forfeited, Money::from_nano(5));
+        assert_eq!(acc.escrowed(&a), Money::zero());
+        assert_eq!(acc.get(&b).map(|b| b.reward), Some(Money::from_nano(5)));
+        acc.verify_invariant(Money::from_nano(5))?;
+        Ok(())
+    }
+
+    #[test]
+    fn closing_with_forged_forfeit_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(5), b => Money::from_nano(7)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let before = acc.state_hash()?;
+        let mut inflated = acc.close_account(a, Some(b))?;
+        inflated.forfeited = Money::from_nano(50);
+        let inflated = AccumulationEvent::AccountClosed(inflated);
+        let mut to_self = acc.close_account(a, None)?;
+        to_self.forfeit_to = Some(a);
+        let to_self = AccumulationEvent::AccountClosed(to_self);
+
+        // --- Act + Assert ---
+        assert_eq!(acc.validate(&inflated), Err(Error::InvalidOperation));
+        assert_eq!(acc.apply(inflated), Err(Error::InvalidOperation));
+        assert_eq!(acc.validate(&to_self), Err(Error::InvalidOperation));
+        assert_eq!(acc.apply(to_self), Err(Error::InvalidOperation));
+        assert_eq!(acc.state_hash()?, before);
+        Ok(())
+    }
+
+    #[test]
+    fn forfeiting_to_the_closed_account_itself_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let result = acc.close_account(account, Some(account));
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::InvalidOperation));
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(5))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pages_walk_all_accounts_once() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let distribution: HashMap<_, _> = (1..=5)
+            .map(|i| (get_random_pk(), Money::from_nano(i)))
+            .collect();
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let mut walked = vec![];
+        let mut sizes = vec![];
+        let mut cursor = None;
+        loop {
+            let page = acc.page(cursor.as_ref(), 2);
+            sizes.push(page.len());
+            walked.extend(page.iter().map(|(id, _)| *id));
+            match page.last() {
+                Some((id, _)) if page.len() == 2 => cursor = Some(*id),
+                _ => break,
+            }
+        }
+
+        // --- Assert ---
+        assert_eq!(sizes, vec![2, 2, 1]);
+        let expected: Vec<_> = acc.ordered().into_iter().map(|(id, _)| *id).collect();
+        assert_eq!(walked, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn minted_total_balances_with_outstanding_and_claimed() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(30), b => Money::from_nano(12)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let e = acc.claim_amount(a, Money::from_nano(10))?;
+        acc.apply(AccumulationEvent::RewardsClaimed(e))?;
+        let e = acc.claim(b)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(e))?;
+
+        // --- Assert ---
+        assert_eq!(acc.total_claimed(), Money::from_nano(22));
+        acc.verify_invariant(Money::from_nano(42))?;
+        assert!(matches!(
+            acc.verify_invariant(Money::from_nano(43)),
+            Err(Error::NetworkOther(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn events_apply_only_in_sequence() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let first = AccumulationEvent::RewardsAccumulated(
+            acc.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?,
+        );
+        let mut gapped = acc.accumulate(vec![2], hashmap![account => Money::from_nano(1)])?;
+        gapped.seq = 2;
+
+        // --- Act ---
+        acc.apply(first.clone())?;
+        let repeated = acc.apply(first);
+        let gapped = acc.apply(AccumulationEvent::RewardsAccumulated(gapped));
+        let second = acc.accumulate(vec![2], hashmap![account => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(second.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(repeated, Err(Error::InvalidOperation));
+        assert_eq!(gapped, Err(Error::InvalidOperation));
+        assert_eq!(second.seq, 1);
+        assert_eq!(acc.next_seq(), 2);
+        assert_eq!(Accumulation::from_snapshot(acc.snapshot()).next_seq(), 2);
+        acc.undo_last()?;
+        assert_eq!(acc.next_seq(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_distribution_is_validated_and_applied_as_a_unit() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (existing, new) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![existing => Money::from_nano(u64::MAX - 1)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let overflowing = acc.accumulate(
+            vec![2],
+            hashmap![existing => Money::from_nano(2), new => Money::from_nano(1)],
+        );
+        let e = acc.accumulate(
+            vec![3],
+            hashmap![existing => Money::from_nano(1), new => Money::from_nano(1)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Assert ---
+        assert_eq!(overflowing, Err(Error::ExcessiveValue));
+        assert_eq!(
+            acc.get(&new).map(|acc| acc.reward),
+            Some(Money::from_nano(1))
+        );
+        assert_eq!(acc.snapshot().rewarded, vec![vec![1], vec![3]]);
+        Ok(())
+    }
+
+    #[test]
+    fn subscribers_observe_applied_events_in_order() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let observed = Arc::new(Mutex::new(vec![]));
+        for i in 0..2 {
+            let observed = Arc::clone(&observed);
+            acc.subscribe(Box::new(move |e: &AccumulationEvent| {
+                if let Ok(mut observed) = observed.lock() {
+                    observed.push((i, e.seq()));
+                }
+            }));
+        }
+        let account = get_random_pk();
+
+        // --- Act ---
+        let e = acc.add_account(account, 1)?;
+        acc.apply(AccumulationEvent::AccountAdded(e))?;
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(2)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e.clone()))?;
+        assert!(acc.apply(AccumulationEvent::RewardsAccumulated(e)).is_err());
+        let e = acc.claim(account)?;
+        acc.stage(AccumulationEvent::RewardsClaimed(e));
+        acc.commit_staged()?;
+
+        // --- Assert ---
+        let observed = observed.lock().map_err(|_| Error::InvalidOperation)?;
+        assert_eq!(
+            *observed,
+            vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn filtered_idempotency_rejects_rewarded_ids() -> Result<(), Error> {
+        // --- Arrange ---
+        let acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        let mut acc = Accumulation::from_events(vec![AccumulationEvent::RewardsAccumulated(e)])?
+            .with_idempotency_filter(BloomFilter::new(100, 0.01)?);
+
+        // --- Act ---
+        for i in 2..=100 {
+            let e = acc.accumulate(vec![i], hashmap![account => Money::from_nano(1)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+
+        // --- Assert ---
+        for i in 1..=100 {
+            assert!(acc.is_rewarded(&vec![i]));
+            let result = acc.accumulate(vec![i], hashmap![account => Money::from_nano(1)]);
+            assert_eq!(result, Err(Error::DataExists));
+        }
+        assert!(acc.snapshot().rewarded.is_empty());
+        assert_eq!(acc.get(&account).map(|acc| acc.work), Some(100));
+        Ok(())
+    }
+
+    #[test]
+    fn kinds_of_rewards_are_claimed_separately() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate_kind(
+            vec![1],
+            RewardKind::StoreCost,
+            hashmap![account => Money::from_nano(3)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let store_cost = |acc: &Accumulation| acc.balance_of(&account, RewardKind::StoreCost);
+        let farming = |acc: &Accumulation| acc.balance_of(&account, RewardKind::Farming);
+        assert_eq!(store_cost(&acc), Some(Money::from_nano(3)));
+        assert_eq!(farming(&acc), Some(Money::from_nano(5)));
+
+        // --- Act ---
+        let claim = acc.claim_kind(account, RewardKind::StoreCost)?;
+        acc.validate(&AccumulationEvent::RewardsClaimed(claim.clone()))?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+
+        // --- Assert ---
+        assert_eq!(claim.rewards.reward, Money::from_nano(3));
+        assert_eq!(store_cost(&acc), Some(Money::zero()));
+        assert_eq!(farming(&acc), Some(Money::from_nano(5)));
+        assert_eq!(acc.get(&account).map(|acc| acc.work), Some(2));
+
+        // claiming regardless of kind takes farming rewards first
+        let e = acc.accumulate_kind(
+            vec![3],
+            RewardKind::StoreCost,
+            hashmap![account => Money::from_nano(3)],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim_amount(account, Money::from_nano(6))?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        assert_eq!(store_cost(&acc), Some(Money::from_nano(2)));
+        assert_eq!(farming(&acc), Some(Money::zero()));
+        let claim = acc.claim(account)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        assert_eq!(store_cost(&acc), None);
+        Ok(())
+    }
+
+    #[test]
+    fn claim_of_empty_kind_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let mut empty = acc.claim(account)?;
+        empty.rewards.reward = Money::zero();
+        empty.kind = Some(RewardKind::StoreCost);
+
+        // --- Act ---
+        let result = acc.claim_kind(account, RewardKind::StoreCost);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::InsufficientBalance));
+        assert_eq!(
+            acc.validate(&AccumulationEvent::RewardsClaimed(empty)),
+            Err(Error::InsufficientBalance)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn store_costs_are_part_of_state_hash_and_snapshot() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let mut store_cost = Accumulation::new(Default::default(), Default::default());
+        let e = store_cost.accumulate_kind(
+            vec![1],
+            RewardKind::StoreCost,
+            hashmap![account => Money::from_nano(5)],
+        )?;
+        store_cost.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let mut farming = Accumulation::new(Default::default(), Default::default());
+        let e = farming.accumulate(vec![1], hashmap![account => Money::from_nano(5)])?;
+        farming.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let restored = Accumulation::from_snapshot(store_cost.snapshot());
+
+        // --- Assert ---
+        assert_ne!(store_cost.state_hash()?, farming.state_hash()?);
+        assert_eq!(restored.state_hash()?, store_cost.state_hash()?);
+        assert_eq!(
+            restored.balance_of(&account, RewardKind::StoreCost),
+            Some(Money::from_nano(5))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn accumulating_above_the_max_balance_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_max_balance(Money::from_nano(10));
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(6)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let e = acc.accumulate(vec![2], hashmap![account => Money::from_nano(4)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let result = acc.accumulate(vec![3], hashmap![account => Money::from_nano(1)]);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::InvalidOperation));
+        assert_eq!(
+            acc.get(&account).map(|acc| acc.reward),
+            Some(Money::from_nano(10))
+        );
+        assert!(!acc.is_rewarded(&vec![3]));
+        Ok(())
+    }
+
+    #[test]
+    fn max_balance_is_enforced_when_validating() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_max_balance(Money::from_nano(10));
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(6), bob => Money::from_nano(6)],
+        )?;
+        let accumulated = AccumulationEvent::RewardsAccumulated(e);
+        acc.apply(accumulated.clone())?;
+        let mut closed = acc.close_account(alice, None)?;
+        closed.forfeit_to = Some(bob);
+        let closed = AccumulationEvent::AccountClosed(closed);
+
+        // --- Act + Assert ---
+        assert_eq!(
+            acc.close_account(alice, Some(bob)),
+            Err(Error::InvalidOperation)
+        );
+        assert_eq!(acc.validate(&closed), Err(Error::InvalidOperation));
+        let log = [accumulated, closed];
+        Accumulation::validate_log(&log)?;
+        assert_eq!(
+            Accumulation::validate_log_with_max_balance(&log, Money::from_nano(10)),
+            Err(Error::InvalidOperation)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn balance_above_max_is_reported_by_audit() -> Result<(), Error> {
+        // --- Arrange ---
+        let account = get_random_pk();
+        let balance = RewardCounter {
+            reward: Money::from_nano(11),
+            work: 1,
+        };
+        let acc = Accumulation::new(Default::default(), hashmap![account => balance])
+            .with_max_balance(Money::from_nano(10));
+
+        // --- Act ---
+        let report = acc.audit();
+
+        // --- Assert ---
+        assert_eq!(report.over_max_balance, vec![account]);
+        assert!(!report.is_healthy());
+        Ok(())
+    }
+
+    #[test]
+    fn escrow_above_max_balance_is_not_released() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_escrow_until_age(1)
+            .with_max_balance(Money::from_nano(10));
+        let account = get_random_pk();
+        for id in 1..3 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(6)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        acc.advance_epoch();
+
+        // --- Act ---
+        let mut released = acc.release_escrow(acc.epoch());
+
+        // --- Assert ---
+        assert!(released.released.is_empty());
+        let _ = released.released.insert(account, Money::from_nano(12));
+        assert_eq!(
+            acc.validate(&AccumulationEvent::EscrowReleased(released)),
+            Err(Error::InvalidOperation)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn imported_ids_are_not_rewarded_again() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut source = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        for id in &[vec![2], vec![1]] {
+            let e = source.accumulate(id.clone(), hashmap![account => Money::from_nano(1)])?;
+            source.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        let mut target = Accumulation::new(Default::default(), Default::default());
+        target.import_idempotency(vec![vec![3]]);
+
+        // --- Act ---
+        let exported = source.export_idempotency();
+        target.import_idempotency(exported.clone());
+
+        // --- Assert ---
+        assert_eq!(exported, vec![vec![1], vec![2]]);
+        assert_eq!(target.export_idempotency(), vec![vec![1], vec![2], vec![3]]);
+        for id in exported {
+            let result = target.accumulate(id, hashmap![account => Money::from_nano(1)]);
+            assert_eq!(result, Err(Error::DataExists));
         }
+        assert!(target.get_all().is_empty());
+        Ok(())
     }
 
-    /// -----------------------------------------------------------------
-    /// ---------------------- Queries ----------------------------------
-    /// -----------------------------------------------------------------
+    #[test]
+    fn batch_of_claims_claims_every_account() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(1), b => Money::from_nano(2)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
 
-    ///
-    pub fn get(&self, account: &AccountId) -> Option<&RewardCounter> {
-        self.accumulated.get(account)
-    }
+        // --- Act ---
+        let claims = acc.claim_batch(vec![b, a])?;
 
-    ///
-    pub fn get_all(&self) -> &HashMap<AccountId, RewardCounter> {
-        &self.accumulated
+        // --- Assert ---
+        assert_eq!(claims.len(), 2);
+        assert_eq!(claims[0].rewards.reward, Money::from_nano(2));
+        for claim in claims {
+            acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        }
+        assert!(acc.get_all().is_empty());
+        Ok(())
     }
 
-    /// -----------------------------------------------------------------
-    /// ---------------------- Cmds -------------------------------------
-    /// -----------------------------------------------------------------
+    #[test]
+    fn batch_of_claims_with_unknown_account_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b, unknown) = (get_random_pk(), get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(1), b => Money::from_nano(2)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let before = acc.state_hash()?;
 
-    pub fn add_account(&self, id: AccountId, work: Work) -> Result<AccountAdded> {
-        if self.accumulated.contains_key(&id) {
-            return Err(Error::BalanceExists);
-        }
-        Ok(AccountAdded { id, work })
+        // --- Act ---
+        let result = acc.claim_batch(vec![a, unknown, b]);
+
+        // --- Assert ---
+        assert_eq!(result, Err(Error::NoSuchKey));
+        assert_eq!(acc.state_hash()?, before);
+        assert_eq!(acc.next_seq(), 1);
+        Ok(())
     }
 
-    ///
-    pub fn accumulate(
-        &self,
-        id: Id,
-        distribution: HashMap<AccountId, Money>,
-    ) -> Result<RewardsAccumulated> {
-        if self.idempotency.contains(&id) {
-            return Err(Error::DataExists);
-        }
-        for (id, amount) in &distribution {
-            if let Some(existing) = self.accumulated.get(&id) {
-                if existing.add(*amount).is_none() {
-                    return Err(Error::ExcessiveValue);
-                }
-            };
-        }
+    #[test]
+    fn batch_of_claims_with_duplicate_account_is_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (a, b) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![a => Money::from_nano(1), b => Money::from_nano(2)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let before = acc.state_hash()?;
+
+        // --- Act ---
+        let result = acc.claim_batch(vec![a, b, a]);
 
-        Ok(RewardsAccumulated { id, distribution })
+        // --- Assert ---
+        assert_eq!(result, Err(Error::InvalidOperation));
+        assert_eq!(acc.state_hash()?, before);
+        assert_eq!(acc.next_seq(), 1);
+        Ok(())
     }
 
-    ///
-    pub fn claim(&self, account: AccountId) -> Result<RewardsClaimed> {
-        let result = self.accumulated.get(&account);
-        match result {
-            None => Err(Error::NoSuchKey),
-            Some(rewards) => Ok(RewardsClaimed {
-                account,
-                rewards: rewards.clone(),
-            }),
+    #[test]
+    fn decayed_work_is_rounded_down() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (busy, idle) = (get_random_pk(), get_random_pk());
+        let mut events = vec![];
+        for (account, work) in &[(busy, 10), (idle, 1)] {
+            let e = AccumulationEvent::AccountAdded(acc.add_account(*account, *work)?);
+            acc.apply(e.clone())?;
+            events.push(e);
         }
-    }
 
-    /// -----------------------------------------------------------------
-    /// ---------------------- Mutation ---------------------------------
-    /// -----------------------------------------------------------------
+        // --- Act ---
+        let e = AccumulationEvent::WorkDecayed(acc.decay_work(1, 2)?);
+        acc.apply(e.clone())?;
+        events.push(e);
 
-    /// Mutates state.
-    pub fn apply(&mut self, event: AccumulationEvent) {
-        use AccumulationEvent::*;
-        match event {
-            AccountAdded(e) => {
-                let _ = self.accumulated.insert(
-                    e.id,
-                    RewardCounter {
-                        reward: Money::zero(),
-                        work: e.work,
-                    },
-                );
-            }
-            RewardsAccumulated(e) => {
-                for (id, amount) in e.distribution {
-                    let existing = match self.accumulated.get(&id) {
-                        None => Default::default(),
-                        Some(acc) => acc.clone(),
-                    };
-                    let accumulated = existing.add(amount).unwrap(); // this is OK, since validation shall happen before creating the event
-                    let _ = self.idempotency.insert(e.id.clone());
-                    let _ = self.accumulated.insert(id, accumulated);
-                }
-            }
-            RewardsClaimed(e) => {
-                let _ = self.accumulated.remove(&e.account);
-            }
-        }
+        // --- Assert ---
+        assert_eq!(acc.get(&busy).map(|acc| acc.work), Some(5));
+        assert_eq!(acc.get(&idle).map(|acc| acc.work), Some(0));
+        let replayed = Accumulation::from_events(events)?;
+        assert_eq!(replayed.get_all(), acc.get_all());
+        assert_eq!(acc.decay_work(3, 2), Err(Error::InvalidOperation));
+        assert_eq!(acc.decay_work(0, 0), Err(Error::InvalidOperation));
+        acc.undo_last()?;
+        assert_eq!(acc.get(&busy).map(|acc| acc.work), Some(10));
+        Ok(())
     }
-}
-#[cfg(test)]
-mod test {
-    use super::{Accumulation, AccumulationEvent};
-    use safe_nd::{Error, Money, PublicKey};
-    use threshold_crypto::SecretKey;
 
-    macro_rules! hashmap {
-        ($( $key: expr => $val: expr ),*) => {{
-             let mut map = ::std::collections::HashMap::new();
-             $( let _ = map.insert($key, $val); )*
-             map
-        }}
+    #[test]
+    fn claims_below_the_minimum_are_rejected() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_min_claim(Money::from_nano(5));
+        let (dust, enough) = (get_random_pk(), get_random_pk());
+        let distribution = hashmap![dust => Money::from_nano(4), enough => Money::from_nano(5)];
+        let e = acc.accumulate(vec![1], distribution)?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        let rejected = acc.claim(dust);
+        let claim = acc.claim(enough)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+
+        // --- Assert ---
+        assert_eq!(rejected, Err(Error::InsufficientBalance));
+        assert_eq!(
+            acc.claim_amount(dust, Money::from_nano(1)),
+            Err(Error::InsufficientBalance)
+        );
+        assert_eq!(
+            acc.get(&dust).map(|acc| acc.reward),
+            Some(Money::from_nano(4))
+        );
+        assert!(acc.get(&enough).is_none());
+        Ok(())
     }
 
     #[test]
-    fn when_data_was_not_previously_rewarded_reward_accumulates() -> Result<(), Error> {
+    fn claim_of_absent_account_or_exceeding_balance_is_not_applied() -> Result<(), Error> {
         // --- Arrange ---
         let mut acc = Accumulation::new(Default::default(), Default::default());
         let account = get_random_pk();
-        let data_hash = vec![1, 2, 3];
-        let reward = Money::from_nano(10);
-        let distribution = hashmap![account => reward];
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let mut excessive = acc.claim(account)?;
+        excessive.rewards.reward = Money::from_nano(11);
+        let mut absent = acc.claim(account)?;
+        absent.account = get_random_pk();
 
         // --- Act ---
-        // Try accumulate.
-        let e = acc.accumulate(data_hash, distribution)?;
+        let excessive = acc.apply(AccumulationEvent::RewardsClaimed(excessive));
+        let absent = acc.apply(AccumulationEvent::RewardsClaimed(absent));
 
         // --- Assert ---
-        // Confirm valid ..
-        assert!(e.distribution.len() == 1);
-        assert!(e.distribution.contains_key(&account));
-        assert_eq!(&reward, e.distribution.get(&account).unwrap());
-        acc.apply(AccumulationEvent::RewardsAccumulated(e));
-        // .. and successful.
-        if let Some(accumulated) = acc.get(&account) {
-            assert_eq!(accumulated.reward, reward);
-        }
+        assert_eq!(excessive, Err(Error::ExcessiveValue));
+        assert_eq!(absent, Err(Error::NoSuchKey));
+        assert_eq!(acc.total_claimed(), Money::zero());
+        acc.verify_invariant(Money::from_nano(10))?;
         Ok(())
     }
 
     #[test]
-    fn when_data_is_already_rewarded_accumulation_is_rejected() -> Result<(), Error> {
+    fn rebuilt_work_is_kept_after_partial_claim() -> Result<(), Error> {
         // --- Arrange ---
         let mut acc = Accumulation::new(Default::default(), Default::default());
         let account = get_random_pk();
-        let data_hash = vec![1, 2, 3];
-        let reward = Money::from_nano(10);
-        let distribution = hashmap![account => reward];
+        let mut events = vec![];
+        for id in 0..2 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(10)])?;
+            let e = AccumulationEvent::RewardsAccumulated(e);
+            acc.apply(e.clone())?;
+            events.push(e);
+        }
+        let claim = acc.claim_amount(account, Money::from_nano(5))?;
+        let claim = AccumulationEvent::RewardsClaimed(claim);
+        acc.apply(claim.clone())?;
+        events.push(claim);
+        // Clear work, as in a balances-only snapshot.
+        let balance = RewardCounter {
+            reward: Money::from_nano(15),
+            work: 0,
+        };
+        let mut recovered = Accumulation::new(Default::default(), hashmap![account => balance]);
 
-        // Accumulate reward.
-        let reward = acc.accumulate(data_hash.clone(), distribution.clone())?;
-        acc.apply(AccumulationEvent::RewardsAccumulated(reward));
+        // --- Act ---
+        recovered.rebuild_work(&events);
+
+        // --- Assert ---
+        assert_eq!(
+            recovered.get(&account),
+            Some(&RewardCounter {
+                reward: Money::from_nano(15),
+                work: 2,
+            })
+        );
+        Ok(())
+    }
+    #[test]
+    fn escrow_is_reproduced_by_replaying_and_undoing_events() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc =
+            Accumulation::new(Default::default(), Default::default()).with_escrow_until_age(1);
+        let account = get_random_pk();
+        let mut events = vec![];
+        let e = AccumulationEvent::RewardsAccumulated(
+            acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?,
+        );
+        acc.apply(e.clone())?;
+        events.push(e);
+        acc.advance_epoch();
+        let e = AccumulationEvent::RewardsAccumulated(
+            acc.accumulate(vec![2], hashmap![account => Money::from_nano(5)])?,
+        );
+        acc.apply(e.clone())?;
+        events.push(e);
+        let e = AccumulationEvent::EscrowReleased(acc.release_escrow(acc.epoch()));
+        acc.apply(e.clone())?;
+        events.push(e);
 
         // --- Act ---
-        // Try same data hash again ..
+        let mut replayed =
+            Accumulation::new(Default::default(), Default::default()).with_escrow_until_age(1);
+        for e in events {
+            replayed.apply(e)?;
+        }
+        acc.undo_last()?;
 
         // --- Assert ---
-        // .. confirm not successful.
+        // the second reward is credited directly, as the account matured in the second epoch
+        assert_eq!(replayed.epoch(), 1);
+        assert_eq!(replayed.escrowed(&account), Money::zero());
         assert_eq!(
-            acc.accumulate(data_hash, distribution),
-            Err(Error::DataExists)
+            replayed.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(15))
+        );
+        assert_eq!(acc.escrowed(&account), Money::from_nano(10));
+        assert_eq!(
+            acc.get(&account).map(|a| a.reward),
+            Some(Money::from_nano(5))
         );
         Ok(())
     }
 
     #[test]
-    fn when_account_has_reward_it_can_claim() -> Result<(), Error> {
+    fn undoing_event_of_later_epoch_restores_the_epoch() -> Result<(), Error> {
         // --- Arrange ---
+        let mut producer = Accumulation::new(Default::default(), Default::default());
         let mut acc = Accumulation::new(Default::default(), Default::default());
         let account = get_random_pk();
-        let data_hash = vec![1, 2, 3];
-        let reward = Money::from_nano(10);
-        let distribution = hashmap![account => reward];
-        let accumulation = acc.accumulate(data_hash, distribution)?;
-        acc.apply(AccumulationEvent::RewardsAccumulated(accumulation));
+        producer.advance_epoch();
+        let e = producer.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+
+        // --- Act ---
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let entered = acc.epoch();
+        acc.undo_last()?;
+
+        // --- Assert ---
+        assert_eq!(entered, 1);
+        assert_eq!(acc.epoch(), 0);
+        assert_eq!(acc.epoch_rewards(&account), Money::zero());
+        Ok(())
+    }
+    #[test]
+    fn unapplied_or_excessive_claims_are_not_refunded() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim_amount(account, Money::from_nano(4))?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+        let mut forged = claim.clone();
+        forged.rewards.reward = Money::from_nano(5);
+        let mut future = claim;
+        future.seq = acc.next_seq;
 
         // --- Act + Assert ---
-        // Try claim, confirm account and amount is correct.
-        let e = acc.claim(account)?;
-        assert!(e.account == account);
-        assert!(e.rewards.reward == reward);
-        acc.apply(AccumulationEvent::RewardsClaimed(e));
+        assert_eq!(acc.refund_claim(&forged), Err(Error::InvalidOperation));
+        assert_eq!(acc.refund_claim(&future), Err(Error::InvalidOperation));
         Ok(())
     }
 
     #[test]
-    fn when_reward_was_claimed_it_can_not_be_claimed_again() {
+    fn refunded_claims_survive_pruning_of_rewarded_ids() -> Result<(), Error> {
         // --- Arrange ---
         let mut acc = Accumulation::new(Default::default(), Default::default());
         let account = get_random_pk();
-        let data_hash = vec![1, 2, 3];
-        let reward = Money::from_nano(10);
-        let distribution = hashmap![account => reward];
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(10)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim(account)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim.clone()))?;
+        let refund = acc.refund_claim(&claim)?;
+        acc.apply(AccumulationEvent::ClaimRefunded(refund.clone()))?;
 
-        let accumulation = acc.accumulate(data_hash, distribution).unwrap();
-        acc.apply(AccumulationEvent::RewardsAccumulated(accumulation));
+        // --- Act ---
+        acc.prune_idempotency(0);
 
-        // Claim the account reward.
-        let claim = acc.claim(account).unwrap();
-        acc.apply(AccumulationEvent::RewardsClaimed(claim));
+        // --- Assert ---
+        assert_eq!(acc.refund_claim(&claim), Err(Error::DataExists));
+        let mut again = refund;
+        again.seq = acc.next_seq;
+        assert_eq!(
+            acc.apply(AccumulationEvent::ClaimRefunded(again)),
+            Err(Error::DataExists)
+        );
+        Ok(())
+    }
+    #[test]
+    fn logs_rejected_on_replay_fail_validation() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(5), bob => Money::from_nano(5)],
+        )?;
+        let accumulated = AccumulationEvent::RewardsAccumulated(e);
+        acc.apply(accumulated.clone())?;
+        let mut forged = acc.close_account(alice, Some(bob))?;
+        forged.forfeited = Money::from_nano(50);
+        let decayed = AccumulationEvent::WorkDecayed(WorkDecayed {
+            numerator: 1,
+            denominator: 0,
+            seq: 1,
+        });
+        let logs = vec![
+            vec![
+                accumulated.clone(),
+                AccumulationEvent::AccountClosed(forged),
+            ],
+            vec![accumulated.clone(), decayed.clone()],
+            vec![decayed],
+            vec![accumulated.clone(), accumulated],
+        ];
+
+        // --- Act + Assert ---
+        for log in logs {
+            let replayed = Accumulation::from_events(log.clone()).map(|_| ());
+            assert!(replayed.is_err());
+            assert_eq!(Accumulation::validate_log(&log), replayed);
+        }
+        Ok(())
+    }
+    #[test]
+    fn ids_in_idempotency_filter_are_audited_counted_and_hashed() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default())
+            .with_idempotency_filter(BloomFilter::new(100, 0.01)?);
+        let account = get_random_pk();
+        let before = acc.state_hash()?;
 
         // --- Act ---
-        // Try claim the account reward again ..
-        let result = acc.claim(account);
+        let e = acc.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
 
         // --- Assert ---
-        // .. confirm not successful.
-        assert_eq!(result, Err(Error::NoSuchKey))
+        assert!(acc.audit().unrecorded_ids.is_empty());
+        assert_eq!(acc.metrics().rewarded_ids, 1);
+        assert_eq!(acc.api_snapshot().totals.rewarded_ids, 1);
+        assert_ne!(acc.state_hash()?, before);
+        Ok(())
     }
 
     #[test]
-    fn when_account_has_no_reward_it_can_not_claim() {
+    fn merge_by_generation_merges_idempotency_filters() -> Result<(), Error> {
         // --- Arrange ---
-        let acc = Accumulation::new(Default::default(), Default::default());
         let account = get_random_pk();
+        let filtered = || -> Result<Accumulation, Error> {
+            Ok(Accumulation::new(Default::default(), Default::default())
+                .with_idempotency_filter(BloomFilter::new(100, 0.01)?))
+        };
+        let ours = filtered()?;
+        let mut theirs = filtered()?;
+        let e = theirs.accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        theirs.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let unfiltered = Accumulation::new(Default::default(), Default::default());
 
-        // --- Act + Assert ---
-        // Try claim the account reward again, confirm not successful.
-        let result = acc.claim(account);
-        match result {
-            Ok(_) => panic!(),
-            Err(err) => assert_eq!(err, Error::NoSuchKey),
-        }
+        // --- Act ---
+        let merged = ours.merge_by_generation(theirs.clone())?;
+
+        // --- Assert ---
+        assert!(merged.is_rewarded(&vec![1]));
+        assert_eq!(merged.metrics().rewarded_ids, 1);
+        assert!(matches!(
+            unfiltered.merge_by_generation(theirs),
+            Err(Error::InvalidOperation)
+        ));
+        Ok(())
     }
+    #[test]
+    fn pruned_ids_are_not_reported_as_unrecorded() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        for id in 0..3 {
+            let e = acc.accumulate(vec![id], hashmap![account => Money::from_nano(1)])?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        let mut capped = acc.clone().with_idempotency_cap(1);
+
+        // --- Act ---
+        acc.prune_idempotency(1);
+        let e = capped.accumulate(vec![3], hashmap![account => Money::from_nano(1)])?;
+        capped.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let _ = capped.idempotency.remove(&vec![3]);
 
+        // --- Assert ---
+        let report = acc.audit();
+        assert!(report.is_healthy());
+        assert_eq!(report.pruned_ids, 2);
+        // the unpruned, yet missing, id is still reported
+        let report = capped.audit();
+        assert_eq!(report.unrecorded_ids, vec![vec![3]]);
+        assert_eq!(report.pruned_ids, 3);
+        Ok(())
+    }
     #[test]
-    fn when_reward_was_claimed_get_returns_none() {
+    fn history_keeps_a_window_of_contributions() -> Result<(), Error> {
         // --- Arrange ---
         let mut acc = Accumulation::new(Default::default(), Default::default());
         let account = get_random_pk();
-        let data_hash = vec![1, 2, 3];
-        let reward = Money::from_nano(10);
-        let distribution = hashmap![account => reward];
-        let accumulation = acc.accumulate(data_hash, distribution).unwrap();
-        acc.apply(AccumulationEvent::RewardsAccumulated(accumulation));
-        let claim = acc.claim(account).unwrap();
-        acc.apply(AccumulationEvent::RewardsClaimed(claim));
+        let rewards = RETAINED_CONTRIBUTIONS as u64 + 1;
 
         // --- Act ---
-        // Try get the account reward.
-        let result = acc.get(&account);
+        for id in 0..rewards {
+            let e = acc.accumulate(
+                id.to_le_bytes().to_vec(),
+                hashmap![account => Money::from_nano(1)],
+            )?;
+            acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
 
         // --- Assert ---
-        assert!(result.is_none());
+        let statement = acc.statement(&account).ok_or(Error::NoSuchKey)?;
+        assert_eq!(statement.rewards, rewards);
+        assert_eq!(statement.contributions.len(), RETAINED_CONTRIBUTIONS);
+        assert_eq!(statement.contributions[0].0, 1u64.to_le_bytes().to_vec());
+        acc.undo_last()?;
+        let statement = acc.statement(&account).ok_or(Error::NoSuchKey)?;
+        assert_eq!(statement.rewards, rewards - 1);
+        assert_eq!(statement.contributions[0].0, 0u64.to_le_bytes().to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn pruning_ids_prunes_the_history() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        let e = acc.accumulate_unique(
+            vec![1],
+            hashmap![alice => Money::from_nano(1), bob => Money::from_nano(1)],
+            vec![9],
+        )?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let claim = acc.claim(bob)?;
+        acc.apply(AccumulationEvent::RewardsClaimed(claim))?;
+        let e = acc.accumulate(vec![2], hashmap![alice => Money::from_nano(1)])?;
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
+
+        // --- Act ---
+        acc.prune_idempotency(1);
+
+        // --- Assert ---
+        let statement = acc.statement(&alice).ok_or(Error::NoSuchKey)?;
+        assert_eq!(statement.rewards, 2);
+        assert_eq!(
+            statement.contributions,
+            vec![(vec![2], Money::from_nano(1))]
+        );
+        assert!(acc.fingerprints.is_empty());
+        assert_eq!(acc.generation(&bob), 0);
+        assert!(!acc.joined.contains_key(&bob));
+        assert!(acc.joined.contains_key(&alice));
+        assert!(acc.audit().is_healthy());
+        Ok(())
     }
+    #[test]
+    fn decay_above_one_is_rejected_when_applied() -> Result<(), Error> {
+        // --- Arrange ---
+        let mut acc = Accumulation::new(Default::default(), Default::default());
+        let account = get_random_pk();
+        let added = acc.add_account(account, 10)?;
+        acc.apply(AccumulationEvent::AccountAdded(added))?;
+        let mut e = acc.decay_work(1, 2)?;
+        e.numerator = 3;
+        let e = AccumulationEvent::WorkDecayed(e);
 
+        // --- Act + Assert ---
+        assert_eq!(acc.decay_work(3, 2), Err(Error::InvalidOperation));
+        assert_eq!(acc.validate(&e), Err(Error::InvalidOperation));
+        assert_eq!(acc.apply(e), Err(Error::InvalidOperation));
+        assert_eq!(acc.get(&account).map(|a| a.work), Some(10));
+        Ok(())
+    }
     fn get_random_pk() -> PublicKey {
         PublicKey::from(SecretKey::random().public_key())
     }
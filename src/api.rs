@@ -0,0 +1,49 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use safe_nd::{AccountId, Money, Work};
+use serde::{Deserialize, Serialize};
+
+/// The version of the `ApiSnapshot` payload.
+/// Bumped on any change to its shape.
+pub const API_SNAPSHOT_VERSION: u32 = 1;
+
+/// A read-only view of the reward state, shaped for
+/// serving as a JSON response.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ApiSnapshot {
+    /// The payload version, see `API_SNAPSHOT_VERSION`.
+    pub version: u32,
+    /// All accounts, ordered by account id.
+    pub accounts: Vec<ApiAccount>,
+    /// Totals over all accounts.
+    pub totals: ApiTotals,
+}
+
+/// An account in the `ApiSnapshot`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ApiAccount {
+    /// The account id.
+    pub id: AccountId,
+    /// The accumulated, unclaimed reward.
+    pub reward: Money,
+    /// The work of the account.
+    pub work: Work,
+}
+
+/// The totals in the `ApiSnapshot`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ApiTotals {
+    /// Number of accounts.
+    pub accounts: u64,
+    /// Sum of all unclaimed rewards, clamped at the max value.
+    pub accumulated: Money,
+    /// Number of rewarded ids.
+    pub rewarded_ids: u64,
+}
@@ -0,0 +1,155 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use safe_nd::{Error, Result};
+use std::convert::TryInto;
+use tiny_keccak::{Hasher, Sha3};
+
+/// A Bloom filter over ids, for idempotency in bounded memory,
+/// see `Accumulation::with_idempotency_filter`.
+///
+/// An inserted id is always found again, i.e. there are no false negatives.
+/// An id never inserted is found with a probability of about the false positive
+/// rate, as long as no more than `capacity` ids are inserted. Beyond that, the
+/// rate grows with every insert. The memory used is fixed at construction:
+///
+/// `bits = -capacity * ln(false_positive_rate) / ln(2)^2`
+///
+/// e.g. about 1.2 MB for a million ids at a rate of one in a hundred.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Sizes the filter for the capacity, at the false positive rate.
+    /// Returns `Error::InvalidOperation` if the capacity is zero,
+    /// or the rate is not between zero and one, exclusive.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Result<Self> {
+        if capacity == 0 || !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+            return Err(Error::InvalidOperation);
+        }
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(capacity as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_hashes = ((num_bits as f64 / capacity as f64) * ln2).round().max(1.0) as u32;
+        Ok(Self {
+            bits: vec![0; (num_bits / 64) as usize + 1],
+            num_bits,
+            num_hashes,
+        })
+    }
+
+    /// Adds the id.
+    pub fn insert(&mut self, id: &[u8]) {
+        for index in self.indices(id) {
+            self.bits[(index / 64) as usize] |= 1 << (index % 64);
+        }
+    }
+
+    /// Adds the ids inserted into the other filter, which must be of the same size,
+    /// or `Error::InvalidOperation` is returned.
+    pub fn union(&mut self, other: &BloomFilter) -> Result<()> {
+        if self.num_bits != other.num_bits || self.num_hashes != other.num_hashes {
+            return Err(Error::InvalidOperation);
+        }
+        for (ours, theirs) in self.bits.iter_mut().zip(&other.bits) {
+            *ours |= theirs;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the id may have been inserted.
+    pub fn contains(&self, id: &[u8]) -> bool {
+        self.indices(id)
+            .all(|index| self.bits[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    /// Returns the number of bits of the filter.
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    /// Returns the words holding the bits of the filter.
+    pub(crate) fn words(&self) -> &[u64] {
+        &self.bits
+    }
+
+    /// Returns the number of bits set per id.
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// The bits of the id, by double hashing of its digest.
+    fn indices(&self, id: &[u8]) -> impl Iterator<Item = u64> {
+        let mut hasher = Sha3::v256();
+        hasher.update(id);
+        let mut hash = [0; 32];
+        hasher.finalize(&mut hash);
+        let first = u64::from_le_bytes(hash[..8].try_into().unwrap_or_default());
+        let second = u64::from_le_bytes(hash[8..16].try_into().unwrap_or_default());
+        let num_bits = self.num_bits;
+        (0..u64::from(self.num_hashes))
+            .map(move |i| first.wrapping_add(i.wrapping_mul(second)) % num_bits)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BloomFilter;
+    use safe_nd::{Error, Result};
+
+    #[test]
+    fn inserted_ids_are_always_found() -> Result<()> {
+        let mut filter = BloomFilter::new(1000, 0.01)?;
+        let ids: Vec<Vec<u8>> = (0..1000u32).map(|i| i.to_le_bytes().to_vec()).collect();
+        for id in &ids {
+            filter.insert(id);
+        }
+        assert!(ids.iter().all(|id| filter.contains(id)));
+
+        // at capacity, false positives stay close to the configured rate
+        let false_positives = (1000..11_000u32)
+            .filter(|i| filter.contains(&i.to_le_bytes()))
+            .count();
+        assert!(false_positives < 300);
+        Ok(())
+    }
+
+    #[test]
+    fn capacity_and_rate_determine_the_size() -> Result<()> {
+        let small = BloomFilter::new(1000, 0.01)?;
+        let large = BloomFilter::new(10_000, 0.01)?;
+        let precise = BloomFilter::new(1000, 0.0001)?;
+        assert_eq!(small.num_bits(), 9586);
+        assert_eq!(small.num_hashes(), 7);
+        assert_eq!(large.num_bits(), 95_851);
+        assert!(precise.num_bits() > small.num_bits());
+        assert!(precise.num_hashes() > small.num_hashes());
+        assert_eq!(BloomFilter::new(0, 0.01), Err(Error::InvalidOperation));
+        assert_eq!(BloomFilter::new(10, 1.0), Err(Error::InvalidOperation));
+        Ok(())
+    }
+
+    #[test]
+    fn union_contains_the_ids_of_both() -> Result<()> {
+        let mut ours = BloomFilter::new(100, 0.01)?;
+        let mut theirs = BloomFilter::new(100, 0.01)?;
+        ours.insert(&[1]);
+        theirs.insert(&[2]);
+        ours.union(&theirs)?;
+        assert!(ours.contains(&[1]) && ours.contains(&[2]));
+        assert_eq!(
+            ours.union(&BloomFilter::new(1000, 0.01)?),
+            Err(Error::InvalidOperation)
+        );
+        Ok(())
+    }
+}
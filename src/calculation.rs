@@ -7,7 +7,8 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
-use safe_nd::{AccountId, Money, Work};
+use safe_nd::{AccountId, Error, Money, Result, Work};
+use serde::{Deserialize, Serialize};
 use std::{cmp::Ordering, collections::HashMap};
 
 /// This algo allows for setting a base cost together with a
@@ -38,12 +39,48 @@ pub struct StorageRewards {
     base_cost: Money,
 }
 
+/// The cost per reward unit, on top of the base cost, see `RewardAlgo::work_cost`.
+const UNIT_COST: u64 = 1;
+
+/// The parameters of `StorageRewards`, for display.
+/// These are all of its inputs, apart from those passed per call:
+/// the factor of `total_reward`, and the work of the accounts to `distribute`.
+/// Caps and decay of rewards are configured on `Accumulation`, not here.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RewardParameters {
+    /// The base cost for buying a unit of work.
+    pub base_cost: Money,
+    /// The fixed cost per reward unit, added to the base cost.
+    pub unit_cost: Money,
+}
+
 impl StorageRewards {
     /// Passed in is the base cost
     /// for buying a unit of work.
     pub fn new(base_cost: Money) -> Self {
         Self { base_cost }
     }
+
+    /// Like `RewardAlgo::distribute`, but the remainder left after rounding
+    /// goes to an account selected by the supplied random beacon, instead
+    /// of always to the same account. The same beacon and accounts
+    /// always select the same account.
+    pub fn distribute_with_beacon(
+        &self,
+        total_reward: Money,
+        accounts_work: HashMap<AccountId, Work>,
+        beacon: [u8; 32],
+    ) -> HashMap<AccountId, Money> {
+        distribute_by_work(total_reward, accounts_work, Some(beacon))
+    }
+
+    /// Returns the currently configured parameters.
+    pub fn parameters(&self) -> RewardParameters {
+        RewardParameters {
+            base_cost: self.base_cost,
+            unit_cost: Money::from_nano(UNIT_COST),
+        }
+    }
 }
 
 /// _Explanation_
@@ -92,7 +129,7 @@ impl RewardAlgo for StorageRewards {
     /// number of bytes to store.
     fn work_cost(&self, num_bytes: u64) -> Money {
         // 1 nano + base cost per reward unit.
-        Money::from_nano(num_bytes + self.base_cost.as_nano())
+        Money::from_nano(num_bytes * UNIT_COST + self.base_cost.as_nano())
     }
 
     /// Use the factor to scale
@@ -105,7 +142,6 @@ impl RewardAlgo for StorageRewards {
         Money::from_nano(amount.round() as u64)
     }
 
-    #[allow(clippy::needless_range_loop)]
     /// Distribute the reward
     /// according to the accumulated work
     /// associated with the ids.
@@ -116,68 +152,131 @@ impl RewardAlgo for StorageRewards {
         total_reward: Money,
         accounts_work: HashMap<AccountId, Work>,
     ) -> HashMap<AccountId, Money> {
-        //
-        let total_reward = total_reward.as_nano();
-        let all_work: Work = accounts_work.values().sum();
+        distribute_by_work(total_reward, accounts_work, None)
+    }
+}
 
-        let mut shares_sum = 0;
-        let mut shares: Vec<(AccountId, u64)> = Default::default();
+#[allow(clippy::needless_range_loop)]
+fn distribute_by_work(
+    total_reward: Money,
+    accounts_work: HashMap<AccountId, Work>,
+    beacon: Option<[u8; 32]>,
+) -> HashMap<AccountId, Money> {
+    //
+    let total_reward = total_reward.as_nano();
+    let all_work: Work = accounts_work.values().sum();
 
-        for (id, work) in &accounts_work {
-            let share = (total_reward as f64 / (all_work as f64 / *work as f64)).round() as u64;
-            shares.push((*id, share));
-            shares_sum += share;
-        }
+    let mut shares_sum = 0;
+    let mut shares: Vec<(AccountId, u64)> = Default::default();
 
-        // Add/remove diff.
-        match total_reward.cmp(&shares_sum) {
-            Ordering::Greater => {
-                // Does not cover probabilistic distribution
-                // (i.e. when total_reward < number of accounts),
-                // since we do not have a shared random value here.
-                // We could put it at the acc closest to the data hash though.. TBD
-                if !shares.is_empty() {
-                    shares.sort_by_key(|t| t.1);
-                    let index = 0; // for now, remainder goes to top worker
-                    let (id, share) = shares[index];
-                    let remainder = total_reward - shares_sum;
-                    let new_share = share + remainder;
-                    shares[index] = (id, new_share);
-                }
+    for (id, work) in &accounts_work {
+        let share = (total_reward as f64 / (all_work as f64 / *work as f64)).round() as u64;
+        shares.push((*id, share));
+        shares_sum += share;
+    }
+
+    // Add/remove diff.
+    match total_reward.cmp(&shares_sum) {
+        Ordering::Greater => {
+            // Does not cover probabilistic distribution
+            // (i.e. when total_reward < number of accounts),
+            // the remainder goes to a single account, selected
+            // by the beacon when a shared random value is supplied.
+            if !shares.is_empty() {
+                let index = match beacon {
+                    None => {
+                        shares.sort_by_key(|t| t.1);
+                        0 // for now, remainder goes to top worker
+                    }
+                    Some(beacon) => {
+                        shares.sort_by_key(|t| t.0);
+                        beacon_index(&beacon, shares.len())
+                    }
+                };
+                let (id, share) = shares[index];
+                let remainder = total_reward - shares_sum;
+                let new_share = share + remainder;
+                shares[index] = (id, new_share);
             }
-            Ordering::Less => {
-                let mut diff = shares_sum - total_reward;
-                shares.sort_by_key(|t| t.1);
-                while diff > 0 {
-                    for i in 0..shares.len() {
-                        let (id, share) = shares[i];
-                        if 0 == diff {
-                            break;
-                        } else if share >= 1 {
-                            shares[i] = (id, share - 1);
-                            diff -= 1;
-                        }
+        }
+        Ordering::Less => {
+            let mut diff = shares_sum - total_reward;
+            shares.sort_by_key(|t| t.1);
+            while diff > 0 {
+                for i in 0..shares.len() {
+                    let (id, share) = shares[i];
+                    if 0 == diff {
+                        break;
+                    } else if share >= 1 {
+                        shares[i] = (id, share - 1);
+                        diff -= 1;
                     }
                 }
             }
-            Ordering::Equal => (),
-        };
-
-        let shares_sum = (&shares).iter().map(|(_, share)| share).sum();
-        if total_reward != shares_sum {
-            panic!("total_reward: {}, shares_sum: {}", total_reward, shares_sum);
         }
+        Ordering::Equal => (),
+    };
 
-        shares
-            .into_iter()
-            .map(|(i, s)| (i, Money::from_nano(s)))
-            .collect()
+    let shares_sum = (&shares).iter().map(|(_, share)| share).sum();
+    if total_reward != shares_sum {
+        panic!("total_reward: {}, shares_sum: {}", total_reward, shares_sum);
+    }
+
+    shares
+        .into_iter()
+        .map(|(i, s)| (i, Money::from_nano(s)))
+        .collect()
+}
+
+/// Splits the total among the accounts, proportionally to their weights,
+/// for use as the distribution of `Accumulation::accumulate`.
+/// Shares are rounded down to whole nanos, and the remainder goes to the
+/// account with the largest id, so that the shares always sum up to exactly the total.
+/// Accounts with a zero share are left out, as zero amounts are not accumulated.
+/// Returns `Error::InvalidOperation` if the weights sum up to zero.
+pub fn distribute_by_weight(
+    total: Money,
+    weights: &HashMap<AccountId, u64>,
+) -> Result<HashMap<AccountId, Money>> {
+    let all_weight: u128 = weights.values().map(|w| u128::from(*w)).sum();
+    if all_weight == 0 {
+        return Err(Error::InvalidOperation);
+    }
+    let total = u128::from(total.as_nano());
+    let mut shares: HashMap<_, _> = weights
+        .iter()
+        .map(|(id, weight)| (*id, (total * u128::from(*weight) / all_weight) as u64))
+        .collect();
+    let remainder = (total - shares.values().map(|s| u128::from(*s)).sum::<u128>()) as u64;
+    if let Some(largest) = weights
+        .iter()
+        .filter(|(_, weight)| **weight > 0)
+        .map(|(id, _)| *id)
+        .max()
+    {
+        if let Some(share) = shares.get_mut(&largest) {
+            *share += remainder;
+        }
     }
+    Ok(shares
+        .into_iter()
+        .filter(|(_, share)| *share > 0)
+        .map(|(id, share)| (id, Money::from_nano(share)))
+        .collect())
 }
+
+/// Maps the beacon to an index in `0..len`.
+fn beacon_index(beacon: &[u8; 32], len: usize) -> usize {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&beacon[..8]);
+    (u64::from_le_bytes(bytes) % len as u64) as usize
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use safe_nd::{Money, PublicKey, Result};
+    use std::collections::HashSet;
     use threshold_crypto::SecretKey;
 
     fn get_random_pk() -> PublicKey {
@@ -201,4 +300,81 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn same_beacon_selects_same_remainder_recipient() {
+        let calc = StorageRewards::new(Money::from_nano(0));
+        let accounts_work: HashMap<_, _> = (0..3).map(|_| (get_random_pk(), 1)).collect();
+        let beacon = [7; 32];
+        let first =
+            calc.distribute_with_beacon(Money::from_nano(10), accounts_work.clone(), beacon);
+        let second = calc.distribute_with_beacon(Money::from_nano(10), accounts_work, beacon);
+        assert_eq!(first, second);
+        assert_eq!(first.values().filter(|r| r.as_nano() == 4).count(), 1);
+    }
+
+    #[test]
+    fn different_beacons_vary_remainder_recipient() {
+        let calc = StorageRewards::new(Money::from_nano(0));
+        let accounts_work: HashMap<_, _> = (0..3).map(|_| (get_random_pk(), 1)).collect();
+        let recipients: HashSet<_> = (0..3u8)
+            .map(|i| {
+                let mut beacon = [0; 32];
+                beacon[0] = i;
+                calc.distribute_with_beacon(Money::from_nano(10), accounts_work.clone(), beacon)
+                    .into_iter()
+                    .find(|(_, reward)| reward.as_nano() == 4)
+                    .map(|(id, _)| id)
+            })
+            .collect();
+        assert_eq!(recipients.len(), 3);
+    }
+
+    #[test]
+    fn parameters_match_the_configuration() {
+        let mut calc = StorageRewards::new(Money::from_nano(3));
+        assert_eq!(calc.parameters().base_cost, Money::from_nano(3));
+        let unit_cost = calc.parameters().unit_cost;
+        assert_eq!(
+            calc.work_cost(2),
+            Money::from_nano(2 * unit_cost.as_nano() + 3)
+        );
+        calc.set(Money::from_nano(5));
+        assert_eq!(calc.parameters().base_cost, Money::from_nano(5));
+    }
+
+    #[test]
+    fn distribute_by_weight_sums_to_the_total() -> Result<()> {
+        let (a, b, c) = (get_random_pk(), get_random_pk(), get_random_pk());
+        let weights: HashMap<_, _> = vec![(a, 1), (b, 1), (c, 2)].into_iter().collect();
+        let clean = distribute_by_weight(Money::from_nano(8), &weights)?;
+        assert_eq!(clean[&a], Money::from_nano(2));
+        assert_eq!(clean[&c], Money::from_nano(4));
+
+        // Shares of 2.5, 2.5 and 5 leave a nano after rounding down, placed on the largest id.
+        let shares = distribute_by_weight(Money::from_nano(10), &weights)?;
+        let sum: u64 = shares.values().map(|s| s.as_nano()).sum();
+        assert_eq!(sum, 10);
+        let largest = *[a, b, c].iter().max().unwrap_or(&a);
+        let expected = if largest == c { 5 + 1 } else { 2 + 1 };
+        assert_eq!(shares[&largest], Money::from_nano(expected));
+        assert_eq!(
+            distribute_by_weight(Money::from_nano(10), &weights)?,
+            shares
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn distribute_by_weight_requires_weight() {
+        let weights: HashMap<_, _> = vec![(get_random_pk(), 0)].into_iter().collect();
+        assert_eq!(
+            distribute_by_weight(Money::from_nano(1), &weights),
+            Err(Error::InvalidOperation)
+        );
+        assert_eq!(
+            distribute_by_weight(Money::from_nano(1), &HashMap::new()),
+            Err(Error::InvalidOperation)
+        );
+    }
 }
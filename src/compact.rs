@@ -0,0 +1,148 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::accumulation::Id;
+use safe_nd::{Error, Result};
+
+/// Encodes the ids in a compact form, for persisting large idempotency sets.
+///
+/// The ids are sorted, and each id is stored as the length of the prefix it
+/// shares with the previous id, followed by the length and bytes of the rest.
+/// All lengths (and the leading number of ids) are LEB128 varints.
+/// Sorted hashes share increasingly long prefixes as the set grows,
+/// and the varints replace the fixed width length of each id.
+pub fn encode_ids<'a>(ids: impl IntoIterator<Item = &'a Id>) -> Vec<u8> {
+    let mut ids: Vec<&Id> = ids.into_iter().collect();
+    ids.sort();
+    ids.dedup();
+
+    let mut bytes = vec![];
+    write_varint(ids.len() as u64, &mut bytes);
+    let mut previous: &[u8] = &[];
+    for id in ids {
+        let shared = previous
+            .iter()
+            .zip(id.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = &id[shared..];
+        write_varint(shared as u64, &mut bytes);
+        write_varint(suffix.len() as u64, &mut bytes);
+        bytes.extend_from_slice(suffix);
+        previous = id;
+    }
+    bytes
+}
+
+/// Decodes ids encoded with `encode_ids`, in sorted order.
+pub fn decode_ids(bytes: &[u8]) -> Result<Vec<Id>> {
+    let mut cursor = bytes;
+    let count = read_varint(&mut cursor)?;
+    let mut ids: Vec<Id> = vec![];
+    for _ in 0..count {
+        let shared = read_varint(&mut cursor)? as usize;
+        let len = read_varint(&mut cursor)? as usize;
+        let previous = ids.last().map(|id| id.as_slice()).unwrap_or(&[]);
+        if shared > previous.len() || len > cursor.len() {
+            return Err(Error::FailedToParse("Invalid id length".to_string()));
+        }
+        let mut id = previous[..shared].to_vec();
+        id.extend_from_slice(&cursor[..len]);
+        cursor = &cursor[len..];
+        ids.push(id);
+    }
+    if !cursor.is_empty() {
+        return Err(Error::FailedToParse("Trailing bytes".to_string()));
+    }
+    Ok(ids)
+}
+
+fn write_varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(cursor: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = match cursor.split_first() {
+            None => return Err(Error::FailedToParse("Unexpected end of input".to_string())),
+            Some(split) => split,
+        };
+        *cursor = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::FailedToParse("Varint too long".to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_ids, encode_ids, Id};
+    use safe_nd::{Error, Result};
+    use std::collections::HashSet;
+
+    #[test]
+    fn encoded_ids_decode_to_the_same_set() -> Result<()> {
+        let ids: HashSet<Id> = vec![
+            vec![],
+            vec![1, 2, 3],
+            vec![1, 2, 4],
+            vec![1, 2],
+            vec![200; 300],
+            vec![7],
+        ]
+        .into_iter()
+        .collect();
+
+        let decoded = decode_ids(&encode_ids(&ids))?;
+
+        assert_eq!(decoded.len(), ids.len());
+        assert_eq!(decoded.into_iter().collect::<HashSet<_>>(), ids);
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let ids = vec![vec![1, 2, 3], vec![1, 2, 4]];
+        let encoded = encode_ids(&ids);
+        let result = decode_ids(&encoded[..encoded.len() - 1]);
+        assert!(matches!(result, Err(Error::FailedToParse(_))));
+    }
+
+    #[test]
+    fn large_set_of_hashes_encodes_smaller_than_flat_list() -> Result<()> {
+        // Pseudo random 32 byte ids, as data hashes would be.
+        let mut seed = 42u64;
+        let ids: Vec<Id> = (0..10_000)
+            .map(|_| {
+                (0..32)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                        (seed >> 56) as u8
+                    })
+                    .collect()
+            })
+            .collect();
+        // A flat list, as serialized by bincode: a u64 length
+        // for the list, and for each of the ids.
+        let flat_size = 8 + ids.iter().map(|id| 8 + id.len()).sum::<usize>();
+
+        let encoded = encode_ids(&ids);
+
+        assert!(encoded.len() * 100 < flat_size * 85);
+        assert_eq!(decode_ids(&encoded)?.len(), ids.len());
+        Ok(())
+    }
+}
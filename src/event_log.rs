@@ -0,0 +1,193 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::AccumulationEvent;
+use safe_nd::{Error, Result};
+use std::{
+    convert::TryFrom,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const LENGTH_PREFIX: usize = 4;
+
+/// An append-only file of events, for persisting the
+/// events emitted by `Accumulation`, and replaying them on startup.
+/// Every event is written as a frame, prefixed by its length as a little endian u32.
+/// A trailing incomplete frame, left by an interrupted write, is dropped when opening.
+/// The events are not kept in memory, but read from the file when replayed.
+pub struct FileEventLog {
+    file: File,
+}
+
+impl FileEventLog {
+    /// Opens the log at the path, creating it if it doesn't exist,
+    /// and checks that all complete frames can be read.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(io_error)?;
+        let log = Self { file };
+        let (_, complete) = log.read_frames()?;
+        if complete < log.file.metadata().map_err(io_error)?.len() {
+            log.file.set_len(complete).map_err(io_error)?;
+        }
+        Ok(log)
+    }
+
+    /// Reads the events from the file, in the order they were appended.
+    pub fn replay(&self) -> Result<Vec<AccumulationEvent>> {
+        self.read_frames().map(|(events, _)| events)
+    }
+
+    /// Returns the events of all complete frames, and the length of the bytes they span.
+    fn read_frames(&self) -> Result<(Vec<AccumulationEvent>, u64)> {
+        let mut file = &self.file;
+        let _ = file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+        let mut bytes = vec![];
+        let _ = file.read_to_end(&mut bytes).map_err(io_error)?;
+        let (events, complete) = read_frames(&bytes)?;
+        Ok((events, complete as u64))
+    }
+
+    /// Appends the event, and syncs it to disk.
+    pub fn append(&mut self, event: &AccumulationEvent) -> Result<()> {
+        let payload = bincode::serialize(event).map_err(|e| Error::FailedToParse(e.to_string()))?;
+        let len = u32::try_from(payload.len()).map_err(|_| Error::ExcessiveValue)?;
+        let mut frame = len.to_le_bytes().to_vec();
+        frame.extend(payload);
+        self.file.write_all(&frame).map_err(io_error)?;
+        self.file.sync_data().map_err(io_error)?;
+        Ok(())
+    }
+}
+
+/// Returns the events of all complete frames, and the length of the bytes they span.
+fn read_frames(bytes: &[u8]) -> Result<(Vec<AccumulationEvent>, usize)> {
+    let mut events = vec![];
+    let mut pos = 0;
+    while bytes.len() - pos >= LENGTH_PREFIX {
+        let mut len = [0; LENGTH_PREFIX];
+        len.copy_from_slice(&bytes[pos..pos + LENGTH_PREFIX]);
+        let start = pos + LENGTH_PREFIX;
+        let end = start + u32::from_le_bytes(len) as usize;
+        if end > bytes.len() {
+            break;
+        }
+        let event = bincode::deserialize(&bytes[start..end])
+            .map_err(|e| Error::FailedToParse(e.to_string()))?;
+        events.push(event);
+        pos = end;
+    }
+    Ok((events, pos))
+}
+
+/// Maps the error to the closest storage related variant,
+/// as there is no variant for IO in general.
+fn io_error(e: std::io::Error) -> Error {
+    use std::io::ErrorKind::*;
+    match e.kind() {
+        NotFound => Error::NoSuchData,
+        PermissionDenied => Error::AccessDenied,
+        AlreadyExists => Error::DataExists,
+        InvalidData | UnexpectedEof => Error::FailedToParse(e.to_string()),
+        _ => Error::NetworkOther(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileEventLog;
+    use crate::{AccountAdded, AccumulationEvent, RewardKind, RewardsAccumulated};
+    use safe_nd::{Money, PublicKey, Result};
+    use std::{fs::OpenOptions, path::PathBuf};
+    use threshold_crypto::SecretKey;
+
+    fn get_random_pk() -> PublicKey {
+        PublicKey::from(SecretKey::random().public_key())
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("safe-farming-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn events() -> Vec<AccumulationEvent> {
+        let account = get_random_pk();
+        let mut distribution = std::collections::HashMap::new();
+        let _ = distribution.insert(account, Money::from_nano(2));
+        vec![
+            AccumulationEvent::AccountAdded(AccountAdded {
+                id: account,
+                work: 1,
+                epoch: 0,
+                seq: 0,
+            }),
+            AccumulationEvent::RewardsAccumulated(RewardsAccumulated {
+                id: vec![1],
+                distribution,
+                fingerprint: None,
+                reward_id: 1,
+                coalesced: vec![],
+                kind: RewardKind::Farming,
+                epoch: 0,
+                seq: 1,
+            }),
+        ]
+    }
+
+    #[test]
+    fn appended_events_are_replayed() -> Result<()> {
+        let path = temp_path("replay");
+        let events = events();
+        let mut log = FileEventLog::open(&path)?;
+        for event in &events {
+            log.append(event)?;
+        }
+        drop(log);
+
+        let log = FileEventLog::open(&path)?;
+        assert_eq!(log.replay()?, events);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[test]
+    fn trailing_incomplete_frame_is_dropped() -> Result<()> {
+        let path = temp_path("truncated");
+        let events = events();
+        let mut log = FileEventLog::open(&path)?;
+        for event in &events {
+            log.append(event)?;
+        }
+        drop(log);
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+        drop(file);
+
+        let mut log = FileEventLog::open(&path)?;
+        assert_eq!(log.replay()?, &events[..1]);
+
+        // appending after recovery continues from the complete prefix
+        log.append(&events[1])?;
+        assert_eq!(log.replay()?, events);
+        drop(log);
+        let log = FileEventLog::open(&path)?;
+        assert_eq!(log.replay()?, events);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+}
@@ -31,7 +31,8 @@ impl<A: RewardAlgo> FarmingSystem<A> {
     /// the owner on the network.
     pub fn add_account(&mut self, id: AccountId, work: Work) -> Result<()> {
         let e = self.accumulation.add_account(id, work)?;
-        self.accumulation.apply(AccumulationEvent::AccountAdded(e));
+        self.accumulation
+            .apply(AccumulationEvent::AccountAdded(e))?;
         Ok(())
     }
 
@@ -78,7 +79,13 @@ impl<A: RewardAlgo> FarmingSystem<A> {
         // scale the reward by the factor
         let total_reward = self.farming_algo.total_reward(factor, work_cost);
         // distribute according to previously performed work
-        let distribution = self.farming_algo.distribute(total_reward, accounts_work);
+        // accounts whose share rounded down to zero are left out
+        let distribution = self
+            .farming_algo
+            .distribute(total_reward, accounts_work)
+            .into_iter()
+            .filter(|(_, reward)| *reward > safe_nd::Money::zero())
+            .collect();
 
         // validate the operation
         let e = self.accumulation.accumulate(data_hash, distribution)?;
@@ -86,7 +93,7 @@ impl<A: RewardAlgo> FarmingSystem<A> {
         // apply the result, reward counter is now incremented
         // i.e. both the reward amount and the work performed.
         self.accumulation
-            .apply(AccumulationEvent::RewardsAccumulated(e));
+            .apply(AccumulationEvent::RewardsAccumulated(e))?;
 
         Ok(total_reward)
     }
@@ -94,7 +101,7 @@ impl<A: RewardAlgo> FarmingSystem<A> {
     pub fn claim(&mut self, id: AccountId) -> Result<RewardCounter> {
         let e = self.accumulation.claim(id)?;
         self.accumulation
-            .apply(AccumulationEvent::RewardsClaimed(e.clone()));
+            .apply(AccumulationEvent::RewardsClaimed(e.clone()))?;
         Ok(e.rewards)
     }
 }
@@ -0,0 +1,182 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use safe_nd::{Error, Money, Result, Work};
+use std::convert::TryFrom;
+
+/// The curve by which the reward for storing data is calculated.
+///
+/// The reward grows linearly with the data size, on top of a base reward,
+/// and is damped by the work the account has already performed:
+///
+/// `reward = (base + data_size * nanos_per_byte) * work_damping / (work_damping + work)`
+///
+/// An account without work gets the full reward, an account with
+/// `work_damping` work gets half of it, and so on. The result is rounded down to whole nanos.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RewardCurve {
+    /// The reward per byte of data, in nanos.
+    pub nanos_per_byte: u64,
+    /// The work at which the reward is halved.
+    pub work_damping: u64,
+}
+
+impl Default for RewardCurve {
+    fn default() -> Self {
+        Self {
+            nanos_per_byte: 1,
+            work_damping: 100,
+        }
+    }
+}
+
+impl RewardCurve {
+    /// Calculates the reward, see `RewardCurve`.
+    /// Returns `Error::InvalidOperation` if the damping is zero,
+    /// and `Error::ExcessiveValue` if the reward overflows.
+    pub fn reward(&self, base: Money, work: Work, data_size: usize) -> Result<Money> {
+        if self.work_damping == 0 {
+            return Err(Error::InvalidOperation);
+        }
+        let size_reward = u128::from(self.nanos_per_byte)
+            .checked_mul(data_size as u128)
+            .ok_or(Error::ExcessiveValue)?;
+        let undamped = u128::from(base.as_nano())
+            .checked_add(size_reward)
+            .ok_or(Error::ExcessiveValue)?;
+        let damping = u128::from(self.work_damping);
+        let reward = undamped.checked_mul(damping).ok_or(Error::ExcessiveValue)?
+            / (damping + u128::from(work));
+        let reward = u64::try_from(reward).map_err(|_| Error::ExcessiveValue)?;
+        Ok(Money::from_nano(reward))
+    }
+}
+
+/// Halves rewards each time the total claimed payout crosses one of a
+/// set of thresholds, for a deflationary supply of rewards.
+///
+/// The claimed total is tracked by `Accumulation` as claims are applied,
+/// so it is restored when replaying the events. Callers scale the base
+/// reward by the schedule before calculating the reward of some work.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RewardSchedule {
+    thresholds: Vec<Money>,
+    claimed: Money,
+}
+
+impl RewardSchedule {
+    /// A schedule halving rewards at each of the claimed totals, in any order.
+    pub fn new(mut thresholds: Vec<Money>) -> Self {
+        thresholds.sort();
+        Self {
+            thresholds,
+            claimed: Money::zero(),
+        }
+    }
+
+    /// Returns the total claimed payout so far.
+    pub fn claimed(&self) -> Money {
+        self.claimed
+    }
+
+    /// Returns the number of thresholds crossed by the claimed total.
+    pub fn halvings(&self) -> u32 {
+        self.thresholds
+            .iter()
+            .filter(|t| **t <= self.claimed)
+            .count() as u32
+    }
+
+    /// Returns the factor rewards are currently scaled by, halved for every crossed threshold.
+    pub fn current_multiplier(&self) -> f64 {
+        0.5_f64.powi(self.halvings() as i32)
+    }
+
+    /// Scales the base reward by the current multiplier, rounded down to whole nanos.
+    /// This is the fixed-point equivalent of multiplying by `current_multiplier`.
+    pub fn scale(&self, base: Money) -> Money {
+        let nanos = base.as_nano().checked_shr(self.halvings()).unwrap_or(0);
+        Money::from_nano(nanos)
+    }
+
+    pub(crate) fn with_claimed(&self, claimed: Money) -> Self {
+        Self {
+            thresholds: self.thresholds.clone(),
+            claimed,
+        }
+    }
+}
+
+/// Calculates the reward for storing data of the size,
+/// by the default `RewardCurve`.
+pub fn calculate_reward(base: Money, work: Work, data_size: usize) -> Result<Money> {
+    RewardCurve::default().reward(base, work, data_size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{calculate_reward, RewardCurve, RewardSchedule};
+    use safe_nd::{Error, Money, Result};
+
+    #[test]
+    fn reward_is_pinned_for_representative_inputs() -> Result<()> {
+        let base = Money::from_nano(1000);
+        assert_eq!(calculate_reward(base, 0, 0)?, Money::from_nano(1000));
+        assert_eq!(calculate_reward(base, 0, 500)?, Money::from_nano(1500));
+        assert_eq!(calculate_reward(base, 100, 500)?, Money::from_nano(750));
+        assert_eq!(calculate_reward(base, 300, 500)?, Money::from_nano(375));
+        assert_eq!(calculate_reward(base, 200, 0)?, Money::from_nano(333));
+        Ok(())
+    }
+
+    #[test]
+    fn curve_parameters_apply() -> Result<()> {
+        let curve = RewardCurve {
+            nanos_per_byte: 2,
+            work_damping: 10,
+        };
+        assert_eq!(curve.reward(Money::zero(), 0, 50)?, Money::from_nano(100));
+        assert_eq!(curve.reward(Money::zero(), 10, 50)?, Money::from_nano(50));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_or_overflowing_curves_fail() {
+        let curve = RewardCurve {
+            nanos_per_byte: 1,
+            work_damping: 0,
+        };
+        assert_eq!(
+            curve.reward(Money::zero(), 0, 1),
+            Err(Error::InvalidOperation)
+        );
+        let curve = RewardCurve {
+            nanos_per_byte: u64::MAX,
+            work_damping: 1,
+        };
+        assert_eq!(
+            curve.reward(Money::zero(), 0, 2),
+            Err(Error::ExcessiveValue)
+        );
+    }
+
+    #[test]
+    fn schedule_halves_at_each_crossed_threshold() {
+        let schedule = RewardSchedule::new(vec![Money::from_nano(200), Money::from_nano(100)]);
+        let base = Money::from_nano(1000);
+        assert_eq!(schedule.current_multiplier(), 1.0);
+        assert_eq!(schedule.scale(base), base);
+        let schedule = schedule.with_claimed(Money::from_nano(100));
+        assert_eq!(schedule.current_multiplier(), 0.5);
+        assert_eq!(schedule.scale(base), Money::from_nano(500));
+        let schedule = schedule.with_claimed(Money::from_nano(250));
+        assert_eq!(schedule.halvings(), 2);
+        assert_eq!(schedule.scale(base), Money::from_nano(250));
+    }
+}
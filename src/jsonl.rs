@@ -0,0 +1,141 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::AccumulationEvent;
+use safe_nd::{Error, Result};
+use std::io::Write;
+
+/// Serializes the events as newline delimited JSON, one event per line.
+pub fn events_to_jsonl(events: &[AccumulationEvent]) -> Result<String> {
+    let mut writer = JsonLinesWriter::new(vec![]);
+    for event in events {
+        writer.write(event)?;
+    }
+    String::from_utf8(writer.into_inner()).map_err(|e| Error::FailedToParse(e.to_string()))
+}
+
+/// Parses newline delimited JSON, one event per line.
+/// Empty lines are skipped.
+pub fn events_from_jsonl(jsonl: &str) -> Result<Vec<AccumulationEvent>> {
+    jsonl
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| Error::FailedToParse(e.to_string())))
+        .collect()
+}
+
+/// Streams events as newline delimited JSON to the writer,
+/// one event per line, as they are written.
+pub struct JsonLinesWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// ctor
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes the event as a line.
+    pub fn write(&mut self, event: &AccumulationEvent) -> Result<()> {
+        let line = serde_json::to_string(event).map_err(|e| Error::FailedToParse(e.to_string()))?;
+        writeln!(self.writer, "{}", line).map_err(|e| Error::NetworkOther(e.to_string()))
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .map_err(|e| Error::NetworkOther(e.to_string()))
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{events_from_jsonl, events_to_jsonl, JsonLinesWriter};
+    use crate::{AccountAdded, AccumulationEvent, RewardKind, RewardsAccumulated, RewardsClaimed};
+    use safe_nd::{Money, PublicKey, Result, RewardCounter};
+    use threshold_crypto::SecretKey;
+
+    macro_rules! hashmap {
+        ($( $key: expr => $val: expr ),*) => {{
+             let mut map = ::std::collections::HashMap::new();
+             $( let _ = map.insert($key, $val); )*
+             map
+        }}
+    }
+
+    fn get_random_pk() -> PublicKey {
+        PublicKey::from(SecretKey::random().public_key())
+    }
+
+    fn events() -> Vec<AccumulationEvent> {
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+        vec![
+            AccumulationEvent::AccountAdded(AccountAdded {
+                id: alice,
+                work: 1,
+                epoch: 0,
+                seq: 0,
+            }),
+            AccumulationEvent::RewardsAccumulated(RewardsAccumulated {
+                id: vec![1, 2],
+                distribution: hashmap![alice => Money::from_nano(3), bob => Money::from_nano(4)],
+                fingerprint: Some(vec![5]),
+                reward_id: 1,
+                coalesced: vec![vec![6]],
+                kind: RewardKind::StoreCost,
+                epoch: 0,
+                seq: 1,
+            }),
+            AccumulationEvent::RewardsClaimed(RewardsClaimed {
+                account: bob,
+                rewards: RewardCounter {
+                    reward: Money::from_nano(4),
+                    work: 1,
+                },
+                destination: alice,
+                kind: Some(RewardKind::StoreCost),
+                seq: 2,
+            }),
+        ]
+    }
+
+    #[test]
+    fn each_line_parses_back_to_the_event() -> Result<()> {
+        let events = events();
+        let jsonl = events_to_jsonl(&events)?;
+        let lines: Vec<_> = jsonl.lines().collect();
+        assert_eq!(lines.len(), events.len());
+        for (line, event) in lines.iter().zip(&events) {
+            let parsed: AccumulationEvent = serde_json::from_str(line).unwrap();
+            assert_eq!(&parsed, event);
+        }
+        assert_eq!(events_from_jsonl(&jsonl)?, events);
+        Ok(())
+    }
+
+    #[test]
+    fn streamed_lines_match_the_exported_lines() -> Result<()> {
+        let events = events();
+        let mut writer = JsonLinesWriter::new(vec![]);
+        for event in &events {
+            writer.write(event)?;
+        }
+        writer.flush()?;
+        let streamed = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(streamed, events_to_jsonl(&events)?);
+        Ok(())
+    }
+}
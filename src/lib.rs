@@ -36,12 +36,36 @@ pub use crate::{
     utils::RewardCounterSet,
 };
 use safe_nd::{AccountId, Money, RewardCounter, Work};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 ///
 pub mod accumulation;
+/// Payloads for serving the state over an API.
+pub mod api;
+/// Bloom filters for idempotency in bounded memory.
+pub mod bloom;
 ///
 pub mod calculation;
+/// Compact encoding of idempotency sets.
+pub mod compact;
+/// Persisting of events to an append-only file.
+pub mod event_log;
+/// Reward computation from work and data size.
+pub mod farming;
+/// Exporting of events as JSON lines.
+pub mod jsonl;
+/// Merkle proofs of reward eligibility.
+pub mod merkle;
+/// Signed receipts of claims.
+pub mod receipt;
+/// Scripting of scenarios for tests.
+#[cfg(any(test, feature = "test-utils"))]
+pub mod scenario;
+/// Sharing of an `Accumulation` between threads.
+pub mod shared;
+/// Persisting of events to pluggable stores.
+pub mod store;
 /// Used for calculating the median
 /// of a vec of RewardCounters.
 pub mod utils;
@@ -49,7 +73,7 @@ pub mod utils;
 mod example;
 
 ///
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum AccumulationEvent {
     ///
     AccountAdded(AccountAdded),
@@ -57,38 +81,218 @@ pub enum AccumulationEvent {
     RewardsAccumulated(RewardsAccumulated),
     ///
     RewardsClaimed(RewardsClaimed),
+    ///
+    RewardsTransferred(RewardsTransferred),
+    ///
+    AccountClosed(AccountClosed),
+    ///
+    WorkDecayed(WorkDecayed),
+    ///
+    ClaimRefunded(ClaimRefunded),
+    ///
+    EscrowReleased(EscrowReleased),
+}
+
+impl AccumulationEvent {
+    /// Returns the position of the event in the log.
+    pub fn seq(&self) -> u64 {
+        use AccumulationEvent::*;
+        match self {
+            AccountAdded(e) => e.seq,
+            RewardsAccumulated(e) => e.seq,
+            RewardsClaimed(e) => e.seq,
+            RewardsTransferred(e) => e.seq,
+            AccountClosed(e) => e.seq,
+            WorkDecayed(e) => e.seq,
+            ClaimRefunded(e) => e.seq,
+            EscrowReleased(e) => e.seq,
+        }
+    }
+
+    pub(crate) fn set_seq(&mut self, seq: u64) {
+        use AccumulationEvent::*;
+        match self {
+            AccountAdded(e) => e.seq = seq,
+            RewardsAccumulated(e) => e.seq = seq,
+            RewardsClaimed(e) => e.seq = seq,
+            RewardsTransferred(e) => e.seq = seq,
+            AccountClosed(e) => e.seq = seq,
+            WorkDecayed(e) => e.seq = seq,
+            ClaimRefunded(e) => e.seq = seq,
+            EscrowReleased(e) => e.seq = seq,
+        }
+    }
 }
 
 ///
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct AccountAdded {
     /// The account id.
     pub id: AccountId,
     /// Total work accumulated by the account owner.
     pub work: Work,
+    /// The epoch the account joined in, from which the escrow age is counted.
+    pub epoch: u64,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
 }
 
 /// Reward and its distribution has been
 /// calculated, and accumulates with this event.
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RewardsAccumulated {
     /// An identifier of a rewarded "thing", such as a data hash for example.
     /// Makes sure we only accumulate a rewarded action _once_.
     pub id: Vec<u8>,
     ///
+    #[serde(with = "account_pairs")]
     pub distribution: HashMap<AccountId, Money>,
+    /// Identifies the logical work rewarded, when it
+    /// must only be rewarded once per account, regardless of id.
+    pub fingerprint: Option<Vec<u8>>,
+    /// Assigned by the accumulator, monotonically increasing,
+    /// for tracking the reward independently of the id.
+    pub reward_id: u64,
+    /// Ids of buffered rewards coalesced into this one,
+    /// which are rewarded along with the id.
+    pub coalesced: Vec<Vec<u8>>,
+    /// The source of the rewards.
+    pub kind: RewardKind,
+    /// The epoch of the accumulation, deciding which rewards are held in escrow.
+    pub epoch: u64,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
 }
 
 /// The accumulation of rewards stops at
 /// this instance of the Accumulator.
 /// The accumulated work is transfered to another instance,
 /// and the accumulated rewards is paid out.
-#[derive(Clone, Eq, PartialEq, PartialOrd, Debug)]
+#[derive(Clone, Eq, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
 pub struct RewardsClaimed {
     ///
     pub account: AccountId,
-    ///
+    /// The claimed reward, which may be part of the balance,
+    /// and the work of the account at the time of the claim.
+    pub rewards: RewardCounter,
+    /// The account to pay the claimed reward out to,
+    /// which is the claiming account unless designated otherwise.
+    pub destination: AccountId,
+    /// The kind of rewards claimed, or `None` if claimed regardless of kind.
+    pub kind: Option<RewardKind>,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
+}
+
+/// The rewards and work of an account have been moved
+/// to another account, such as when the account key is rotated.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RewardsTransferred {
+    /// The account moved from.
+    pub from: AccountId,
+    /// The account moved to.
+    pub to: AccountId,
+    /// The rewards and work moved.
     pub rewards: RewardCounter,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
+}
+
+/// The source of rewards, which are tracked separately
+/// in the balance of an account, see `Accumulation::balance_of`.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+pub enum RewardKind {
+    /// Paid for the cost of storing data.
+    StoreCost,
+    /// Paid for farming, i.e. for the work performed.
+    Farming,
+}
+
+/// The account has been removed, and stops accumulating rewards.
+/// Its balance is forfeited, either to another account or dropped.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AccountClosed {
+    /// The closed account.
+    pub account: AccountId,
+    /// The balance of the account at the time of closing,
+    /// including its rewards held in escrow.
+    pub forfeited: Money,
+    /// The account credited with the forfeited balance,
+    /// or `None` if the balance is dropped.
+    pub forfeit_to: Option<AccountId>,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
+}
+
+/// The work of every account has been scaled down by the
+/// fraction `numerator / denominator`, rounded down.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct WorkDecayed {
+    /// The part of the work retained.
+    pub numerator: Work,
+    /// The whole of the work.
+    pub denominator: Work,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
+}
+
+/// The settlement of a claim failed, and its rewards are credited back,
+/// reverting the claim. The work of the account is left as it is.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ClaimRefunded {
+    /// The seq of the refunded claim, so that it is refunded only once.
+    pub claim: u64,
+    /// The account claimed from.
+    pub account: AccountId,
+    /// The refunded reward, and the work of the account at the
+    /// time of the claim, restored if the claim removed the account.
+    pub rewards: RewardCounter,
+    /// The kind of rewards claimed, see `RewardsClaimed`.
+    pub kind: Option<RewardKind>,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
+}
+
+/// Rewards held in escrow have matured, and are credited to the accounts.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EscrowReleased {
+    /// The epoch at which the escrow matured.
+    pub epoch: u64,
+    /// The released rewards, i.e. the entire escrow of each account.
+    #[serde(with = "account_pairs")]
+    pub released: HashMap<AccountId, Money>,
+    /// The position of the event in the log, assigned by the
+    /// `Accumulation` producing it, starting at zero.
+    pub seq: u64,
+}
+
+/// Maps keyed by account are serialized as sequences of pairs,
+/// since formats such as JSON only allow strings as keys.
+mod account_pairs {
+    use safe_nd::AccountId;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<V: Serialize, S: Serializer>(
+        map: &HashMap<AccountId, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, V: Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<AccountId, V>, D::Error> {
+        let pairs = Vec::<(AccountId, V)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
 }
 
 #[cfg(test)]
@@ -123,7 +327,7 @@ mod test {
         assert!(e.distribution.len() == 1);
         assert!(e.distribution.contains_key(&account));
         assert_eq!(&reward, e.distribution.get(&account).unwrap());
-        acc.apply(AccumulationEvent::RewardsAccumulated(e));
+        acc.apply(AccumulationEvent::RewardsAccumulated(e))?;
 
         // .. and successful.
         match acc.get(&account) {
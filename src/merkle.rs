@@ -0,0 +1,140 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use safe_nd::{AccountId, Error, Money, Result};
+use tiny_keccak::{Hasher, Sha3};
+
+/// A Sha3-256 hash.
+pub type Digest = [u8; 32];
+
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+
+/// Proof that a leaf is part of a tree with a given root.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerkleProof {
+    /// The sibling hashes from the leaf up to the root, and
+    /// whether the sibling is to the left. Levels where the node
+    /// had no sibling (the last node of an uneven level) are skipped.
+    pub path: Vec<(Digest, bool)>,
+}
+
+impl MerkleProof {
+    /// Returns whether the proof leads from the leaf to the root.
+    pub fn verify(&self, leaf: Digest, root: Digest) -> bool {
+        let computed = self.path.iter().fold(leaf, |hash, (sibling, left)| {
+            if *left {
+                node(sibling, &hash)
+            } else {
+                node(&hash, sibling)
+            }
+        });
+        computed == root
+    }
+}
+
+/// A Merkle tree over a list of eligible accounts and their rewards.
+/// Leaves and inner nodes are hashed with different prefixes, and
+/// the last node of an uneven level is moved up unchanged.
+pub struct MerkleTree {
+    levels: Vec<Vec<Digest>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree from the leaves, in the given order.
+    pub fn new(leaves: Vec<Digest>) -> Self {
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node(left, right),
+                    _ => pair[0],
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    /// Returns the root, or None if the tree is empty.
+    pub fn root(&self) -> Option<Digest> {
+        self.levels[self.levels.len() - 1].first().copied()
+    }
+
+    /// Returns the proof for the leaf at the index, if any.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.levels[0].len() {
+            return None;
+        }
+        let mut path = vec![];
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                path.push((level[sibling], sibling < index));
+            }
+            index /= 2;
+        }
+        Some(MerkleProof { path })
+    }
+}
+
+/// Returns the leaf hash of the reward to the account.
+pub fn leaf(account: &AccountId, amount: Money) -> Result<Digest> {
+    let account = bincode::serialize(account).map_err(|e| Error::FailedToParse(e.to_string()))?;
+    let mut hasher = Sha3::v256();
+    hasher.update(&[LEAF_TAG]);
+    hasher.update(&account);
+    hasher.update(&amount.as_nano().to_le_bytes());
+    let mut hash = [0; 32];
+    hasher.finalize(&mut hash);
+    Ok(hash)
+}
+
+fn node(left: &Digest, right: &Digest) -> Digest {
+    let mut hasher = Sha3::v256();
+    hasher.update(&[NODE_TAG]);
+    hasher.update(left);
+    hasher.update(right);
+    let mut hash = [0; 32];
+    hasher.finalize(&mut hash);
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Digest, MerkleTree};
+
+    fn leaves(count: u8) -> Vec<Digest> {
+        (0..count).map(|i| [i; 32]).collect()
+    }
+
+    #[test]
+    fn every_leaf_is_proven_against_root() {
+        for count in 1..8 {
+            let leaves = leaves(count);
+            let tree = MerkleTree::new(leaves.clone());
+            let root = tree.root().unwrap();
+            for (i, leaf) in leaves.into_iter().enumerate() {
+                assert!(tree.proof(i).unwrap().verify(leaf, root));
+            }
+        }
+    }
+
+    #[test]
+    fn proof_does_not_verify_other_leaf() {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(leaves.clone());
+        let root = tree.root().unwrap();
+        let proof = tree.proof(1).unwrap();
+        assert!(!proof.verify(leaves[2], root));
+        assert!(tree.proof(5).is_none());
+    }
+}
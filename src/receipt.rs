@@ -0,0 +1,147 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::{AccountId, RewardsClaimed};
+use safe_nd::{Error, Result, RewardCounter};
+use serde::{Deserialize, Serialize};
+use threshold_crypto::{PublicKey, SecretKey, Signature};
+
+/// A signed statement of a claim, which the farmer can
+/// present for redemption outside of the section.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ClaimReceipt {
+    /// The claiming account.
+    pub account: AccountId,
+    /// The claimed reward, and the work of the account at the claim.
+    pub rewards: RewardCounter,
+    /// The account to pay the claimed reward out to.
+    pub destination: AccountId,
+    /// The position of the claim in the log, telling
+    /// apart claims that are otherwise equal.
+    pub seq: u64,
+    /// Signature over the account, rewards, destination and seq.
+    pub signature: Signature,
+}
+
+impl RewardsClaimed {
+    /// Signs the claim, producing a receipt verifiable with the public key of the signer.
+    pub fn receipt(&self, signer: &SecretKey) -> Result<ClaimReceipt> {
+        let message = message(&self.account, &self.rewards, &self.destination, self.seq)?;
+        Ok(ClaimReceipt {
+            account: self.account,
+            rewards: self.rewards.clone(),
+            destination: self.destination,
+            seq: self.seq,
+            signature: signer.sign(message),
+        })
+    }
+}
+
+impl ClaimReceipt {
+    /// Returns whether the receipt was signed by the holder of the
+    /// key (e.g. the section key), and has not been altered since.
+    pub fn verify(&self, key: &PublicKey) -> bool {
+        match message(&self.account, &self.rewards, &self.destination, self.seq) {
+            Err(_) => false,
+            Ok(message) => key.verify(&self.signature, message),
+        }
+    }
+}
+
+fn message(
+    account: &AccountId,
+    rewards: &RewardCounter,
+    destination: &AccountId,
+    seq: u64,
+) -> Result<Vec<u8>> {
+    let mut message =
+        bincode::serialize(account).map_err(|e| Error::FailedToParse(e.to_string()))?;
+    message.extend(&rewards.reward.as_nano().to_le_bytes());
+    message.extend(&rewards.work.to_le_bytes());
+    message
+        .extend(bincode::serialize(destination).map_err(|e| Error::FailedToParse(e.to_string()))?);
+    message.extend(&seq.to_le_bytes());
+    Ok(message)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::RewardsClaimed;
+    use safe_nd::{Money, PublicKey, Result, RewardCounter};
+    use threshold_crypto::SecretKey;
+
+    #[test]
+    fn receipt_verifies_with_signer_key() -> Result<()> {
+        let section = SecretKey::random();
+        let account = PublicKey::from(SecretKey::random().public_key());
+        let claim = RewardsClaimed {
+            account,
+            rewards: RewardCounter {
+                reward: Money::from_nano(10),
+                work: 2,
+            },
+            destination: account,
+            kind: None,
+            seq: 0,
+        };
+
+        let receipt = claim.receipt(&section)?;
+
+        assert!(receipt.verify(&section.public_key()));
+        assert!(!receipt.verify(&SecretKey::random().public_key()));
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_receipt_does_not_verify() -> Result<()> {
+        let section = SecretKey::random();
+        let account = PublicKey::from(SecretKey::random().public_key());
+        let claim = RewardsClaimed {
+            account,
+            rewards: RewardCounter {
+                reward: Money::from_nano(10),
+                work: 2,
+            },
+            destination: account,
+            kind: None,
+            seq: 0,
+        };
+
+        let mut receipt = claim.receipt(&section)?;
+        receipt.rewards.reward = Money::from_nano(1000);
+        assert!(!receipt.verify(&section.public_key()));
+
+        let mut receipt = claim.receipt(&section)?;
+        receipt.destination = PublicKey::from(SecretKey::random().public_key());
+        assert!(!receipt.verify(&section.public_key()));
+        Ok(())
+    }
+
+    #[test]
+    fn receipt_with_tampered_seq_does_not_verify() -> Result<()> {
+        let section = SecretKey::random();
+        let account = PublicKey::from(SecretKey::random().public_key());
+        let claim = RewardsClaimed {
+            account,
+            rewards: RewardCounter {
+                reward: Money::from_nano(10),
+                work: 2,
+            },
+            destination: account,
+            kind: None,
+            seq: 3,
+        };
+
+        let mut receipt = claim.receipt(&section)?;
+        assert_eq!(receipt.seq, 3);
+        receipt.seq = 4;
+        assert!(!receipt.verify(&section.public_key()));
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::{accumulation::Id, AccountId, Accumulation, AccumulationEvent};
+use safe_nd::{Money, Result, Work};
+use std::collections::HashMap;
+
+/// A high level action in a scenario,
+/// corresponding to one of the cmds of `Accumulation`.
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Adds an account with the given work.
+    AddAccount {
+        /// The account id.
+        id: AccountId,
+        /// Initial work of the account.
+        work: Work,
+    },
+    /// Accumulates the distribution for the id.
+    Accumulate {
+        /// Identifier of the rewarded "thing".
+        id: Id,
+        /// The reward per account.
+        distribution: HashMap<AccountId, Money>,
+    },
+    /// Claims the rewards of the account.
+    Claim {
+        /// The account id.
+        account: AccountId,
+    },
+}
+
+/// Scripts a sequence of actions, which are
+/// replayed on an empty `Accumulation` when run.
+#[derive(Clone, Debug, Default)]
+pub struct ScenarioBuilder {
+    actions: Vec<Action>,
+}
+
+/// The outcome of a run scenario.
+pub struct Scenario {
+    /// The events produced by the actions, in order.
+    pub events: Vec<AccumulationEvent>,
+    /// The state after all events were applied.
+    pub state: Accumulation,
+}
+
+impl ScenarioBuilder {
+    /// Starts an empty scenario.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends an action.
+    pub fn action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Appends adding of an account.
+    pub fn add_account(self, id: AccountId, work: Work) -> Self {
+        self.action(Action::AddAccount { id, work })
+    }
+
+    /// Appends an accumulation.
+    pub fn accumulate(self, id: Id, distribution: HashMap<AccountId, Money>) -> Self {
+        self.action(Action::Accumulate { id, distribution })
+    }
+
+    /// Appends a claim.
+    pub fn claim(self, account: AccountId) -> Self {
+        self.action(Action::Claim { account })
+    }
+
+    /// Runs the actions in order, applying each resulting event
+    /// before the next action is validated.
+    /// Returns the first error produced by an action.
+    pub fn run(self) -> Result<Scenario> {
+        let mut state = Accumulation::new(Default::default(), Default::default());
+        let mut events = vec![];
+        for action in self.actions {
+            let event = match action {
+                Action::AddAccount { id, work } => {
+                    AccumulationEvent::AccountAdded(state.add_account(id, work)?)
+                }
+                Action::Accumulate { id, distribution } => {
+                    AccumulationEvent::RewardsAccumulated(state.accumulate(id, distribution)?)
+                }
+                Action::Claim { account } => {
+                    AccumulationEvent::RewardsClaimed(state.claim(account)?)
+                }
+            };
+            state.apply(event.clone())?;
+            events.push(event);
+        }
+        Ok(Scenario { events, state })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Accumulation, ScenarioBuilder};
+    use safe_nd::{Error, Money, PublicKey, Result};
+    use threshold_crypto::SecretKey;
+
+    macro_rules! hashmap {
+        ($( $key: expr => $val: expr ),*) => {{
+             let mut map = ::std::collections::HashMap::new();
+             $( let _ = map.insert($key, $val); )*
+             map
+        }}
+    }
+
+    #[test]
+    fn scenario_produces_events_and_final_state() -> Result<()> {
+        // --- Arrange ---
+        let alice = get_random_pk();
+        let bob = get_random_pk();
+
+        // --- Act ---
+        let scenario = ScenarioBuilder::new()
+            .add_account(alice, 1)
+            .add_account(bob, 1)
+            .accumulate(
+                vec![1],
+                hashmap![alice => Money::from_nano(10), bob => Money::from_nano(20)],
+            )
+            .accumulate(vec![2], hashmap![alice => Money::from_nano(5)])
+            .claim(bob)
+            .run()?;
+
+        // --- Assert ---
+        assert_eq!(scenario.events.len(), 5);
+        match scenario.state.get(&alice) {
+            None => return Err(Error::NoSuchKey),
+            Some(accumulated) => {
+                assert_eq!(accumulated.reward, Money::from_nano(15));
+                assert_eq!(accumulated.work, 3);
+            }
+        };
+        assert!(scenario.state.get(&bob).is_none());
+
+        // Replaying the events gives the same state.
+        let replayed = Accumulation::from_events(scenario.events)?;
+        assert_eq!(replayed.get_all(), scenario.state.get_all());
+        assert_eq!(replayed.state_hash()?, scenario.state.state_hash()?);
+        Ok(())
+    }
+
+    #[test]
+    fn scenario_stops_at_first_invalid_action() {
+        let alice = get_random_pk();
+        let result = ScenarioBuilder::new()
+            .accumulate(vec![1], hashmap![alice => Money::from_nano(10)])
+            .accumulate(vec![1], hashmap![alice => Money::from_nano(10)])
+            .run();
+        assert!(matches!(result, Err(Error::DataExists)));
+    }
+
+    fn get_random_pk() -> PublicKey {
+        PublicKey::from(SecretKey::random().public_key())
+    }
+}
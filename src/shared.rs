@@ -0,0 +1,155 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::{
+    accumulation::{Accumulation, Id},
+    AccountId, AccumulationEvent, RewardsAccumulated, RewardsClaimed,
+};
+use safe_nd::{Error, Money, Result, RewardCounter};
+use std::{
+    collections::HashMap,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+/// An `Accumulation` shared between threads, where any number of readers
+/// query the state, and a writer applies events. Queries and cmds take the
+/// read lock, since cmds don't mutate the state, and `apply` takes the write lock.
+/// Results are returned by value, as they cannot outlive the lock.
+/// A lock poisoned by a panicking thread is reported as `Error::NetworkOther`.
+pub struct SharedAccumulation {
+    inner: RwLock<Accumulation>,
+}
+
+impl SharedAccumulation {
+    /// Shares the instance.
+    pub fn new(accumulation: Accumulation) -> Self {
+        Self {
+            inner: RwLock::new(accumulation),
+        }
+    }
+
+    /// Returns the rewards and work of the account, see `Accumulation::get`.
+    pub fn get(&self, account: &AccountId) -> Result<Option<RewardCounter>> {
+        Ok(self.read()?.get(account).cloned())
+    }
+
+    /// Returns the rewards and work of all accounts, see `Accumulation::get_all`.
+    pub fn get_all(&self) -> Result<HashMap<AccountId, RewardCounter>> {
+        Ok(self.read()?.get_all().clone())
+    }
+
+    /// Returns the sum of all balances, see `Accumulation::total_accumulated`.
+    pub fn total_accumulated(&self) -> Result<Money> {
+        self.read()?.total_accumulated()
+    }
+
+    /// Validates an accumulation, see `Accumulation::accumulate`.
+    pub fn accumulate(
+        &self,
+        id: Id,
+        distribution: HashMap<AccountId, Money>,
+    ) -> Result<RewardsAccumulated> {
+        self.read()?.accumulate(id, distribution)
+    }
+
+    /// Validates a claim, see `Accumulation::claim`.
+    pub fn claim(&self, account: AccountId) -> Result<RewardsClaimed> {
+        self.read()?.claim(account)
+    }
+
+    /// Applies the event, see `Accumulation::apply`.
+    pub fn apply(&self, event: AccumulationEvent) -> Result<()> {
+        self.write()?.apply(event)
+    }
+
+    /// Takes the read lock, for queries not exposed by this type.
+    pub fn read(&self) -> Result<RwLockReadGuard<'_, Accumulation>> {
+        self.inner
+            .read()
+            .map_err(|e| Error::NetworkOther(e.to_string()))
+    }
+
+    /// Takes the write lock, for mutations not exposed by this type.
+    pub fn write(&self) -> Result<RwLockWriteGuard<'_, Accumulation>> {
+        self.inner
+            .write()
+            .map_err(|e| Error::NetworkOther(e.to_string()))
+    }
+
+    /// Returns the shared instance.
+    pub fn into_inner(self) -> Result<Accumulation> {
+        self.inner
+            .into_inner()
+            .map_err(|e| Error::NetworkOther(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SharedAccumulation;
+    use crate::{Accumulation, AccumulationEvent};
+    use safe_nd::{Error, Money, PublicKey, Result};
+    use std::{sync::Arc, thread};
+    use threshold_crypto::SecretKey;
+
+    macro_rules! hashmap {
+        ($( $key: expr => $val: expr ),*) => {{
+             let mut map = ::std::collections::HashMap::new();
+             $( let _ = map.insert($key, $val); )*
+             map
+        }}
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn readers_run_alongside_a_writer() -> Result<()> {
+        assert_send_sync::<SharedAccumulation>();
+        let shared = Arc::new(SharedAccumulation::new(Accumulation::new(
+            Default::default(),
+            Default::default(),
+        )));
+        let account = get_random_pk();
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || -> Result<()> {
+                    let mut last = Money::zero();
+                    for _ in 0..100 {
+                        // balances only grow, and every read sees a whole event applied
+                        let total = shared.total_accumulated()?;
+                        assert!(total >= last);
+                        assert_eq!(total.as_nano() % 2, 0);
+                        last = total;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for i in 0..50u8 {
+            let e = shared.accumulate(vec![i], hashmap![account => Money::from_nano(2)])?;
+            shared.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        }
+        for reader in readers {
+            reader
+                .join()
+                .map_err(|_| Error::NetworkOther("reader panicked".to_string()))??;
+        }
+
+        assert_eq!(shared.total_accumulated()?, Money::from_nano(100));
+        assert_eq!(shared.get(&account)?.map(|acc| acc.work), Some(50));
+        assert_eq!(shared.get_all()?.len(), 1);
+        Ok(())
+    }
+
+    fn get_random_pk() -> PublicKey {
+        PublicKey::from(SecretKey::random().public_key())
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::{accumulation::Accumulation, event_log::FileEventLog, AccumulationEvent};
+use safe_nd::Result;
+
+/// Persists the events of an `Accumulation`, from which its state can be rebuilt.
+pub trait RewardStore {
+    /// Durably appends the event.
+    fn append_event(&mut self, event: &AccumulationEvent) -> Result<()>;
+    /// Returns all appended events, in the order they were appended.
+    fn load_events(&self) -> Result<Vec<AccumulationEvent>>;
+}
+
+/// Keeps the events in memory, for tests.
+#[derive(Clone, Debug, Default)]
+pub struct MemStore {
+    events: Vec<AccumulationEvent>,
+}
+
+impl RewardStore for MemStore {
+    fn append_event(&mut self, event: &AccumulationEvent) -> Result<()> {
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn load_events(&self) -> Result<Vec<AccumulationEvent>> {
+        Ok(self.events.clone())
+    }
+}
+
+impl RewardStore for FileEventLog {
+    fn append_event(&mut self, event: &AccumulationEvent) -> Result<()> {
+        self.append(event)
+    }
+
+    fn load_events(&self) -> Result<Vec<AccumulationEvent>> {
+        self.replay()
+    }
+}
+
+/// An `Accumulation` which persists every event to a store before applying it.
+pub struct StoredAccumulation<S: RewardStore> {
+    accumulation: Accumulation,
+    store: S,
+}
+
+impl Accumulation {
+    /// Wraps the instance, so that every event
+    /// applied is first appended to the store.
+    pub fn with_store<S: RewardStore>(self, store: S) -> StoredAccumulation<S> {
+        StoredAccumulation {
+            accumulation: self,
+            store,
+        }
+    }
+}
+
+impl<S: RewardStore> StoredAccumulation<S> {
+    /// Rebuilds the state from the events in the store.
+    pub fn load(store: S) -> Result<Self> {
+        let accumulation = Accumulation::from_events(store.load_events()?)?;
+        Ok(accumulation.with_store(store))
+    }
+
+    /// The state, for queries and cmds.
+    pub fn accumulation(&self) -> &Accumulation {
+        &self.accumulation
+    }
+
+    /// The store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Validates the event, appends it to the store, and then applies it,
+    /// so that no event is applied without having been persisted.
+    pub fn apply(&mut self, event: AccumulationEvent) -> Result<()> {
+        self.accumulation.validate(&event)?;
+        self.store.append_event(&event)?;
+        self.accumulation.apply(event)
+    }
+
+    /// Returns the state and the store.
+    pub fn into_parts(self) -> (Accumulation, S) {
+        (self.accumulation, self.store)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MemStore, RewardStore, StoredAccumulation};
+    use crate::{accumulation::Accumulation, AccumulationEvent};
+    use safe_nd::{Error, Money, PublicKey, Result};
+    use threshold_crypto::SecretKey;
+
+    macro_rules! hashmap {
+        ($( $key: expr => $val: expr ),*) => {{
+             let mut map = ::std::collections::HashMap::new();
+             $( let _ = map.insert($key, $val); )*
+             map
+        }}
+    }
+
+    fn get_random_pk() -> PublicKey {
+        PublicKey::from(SecretKey::random().public_key())
+    }
+
+    #[test]
+    fn applied_events_are_stored_and_reloaded() -> Result<()> {
+        // --- Arrange ---
+        let mut stored = Accumulation::new(Default::default(), Default::default())
+            .with_store(MemStore::default());
+        let (alice, bob) = (get_random_pk(), get_random_pk());
+
+        // --- Act ---
+        let e = stored.accumulation().add_account(alice, 1)?;
+        stored.apply(AccumulationEvent::AccountAdded(e))?;
+        let e = stored.accumulation().accumulate(
+            vec![1],
+            hashmap![alice => Money::from_nano(2), bob => Money::from_nano(3)],
+        )?;
+        stored.apply(AccumulationEvent::RewardsAccumulated(e))?;
+        let e = stored.accumulation().claim(bob)?;
+        stored.apply(AccumulationEvent::RewardsClaimed(e))?;
+
+        // --- Assert ---
+        assert_eq!(stored.store().load_events()?.len(), 3);
+        let (accumulation, store) = stored.into_parts();
+        let reloaded = Accumulation::from_events(store.load_events()?)?;
+        assert_eq!(reloaded.get_all(), accumulation.get_all());
+        assert_eq!(reloaded.snapshot(), accumulation.snapshot());
+        let reloaded = StoredAccumulation::load(store)?;
+        assert_eq!(reloaded.accumulation().get_all(), accumulation.get_all());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_events_are_not_stored() -> Result<()> {
+        let mut stored = Accumulation::new(Default::default(), Default::default())
+            .with_store(MemStore::default());
+        let account = get_random_pk();
+        let e = stored
+            .accumulation()
+            .accumulate(vec![1], hashmap![account => Money::from_nano(1)])?;
+        stored.apply(AccumulationEvent::RewardsAccumulated(e.clone()))?;
+        let result = stored.apply(AccumulationEvent::RewardsAccumulated(e));
+        assert_eq!(result, Err(Error::DataExists));
+        assert_eq!(stored.store().load_events()?.len(), 1);
+        Ok(())
+    }
+}